@@ -8,18 +8,44 @@ function bindPopup(circle, image, map) {
 }
 
 function renderPopup(image, map) {
-    const {id, sha256, urlSmall, urlLarge, timestamp} = image;
+    const {id, sha256, urlSmall, timestamp, caption, tags = [], catNames = []} = image;
     const date = new Date(timestamp).toDateString();
     const location = formatLocation(image);
     const outer = document.createElement('div');
-    const catImage = makeImageLink(urlLarge, urlSmall, `photo #${id}, showing one or more cats`);
+    const catImage = makeImageLink(id, urlSmall, `photo #${id}, showing one or more cats`);
     outer.appendChild(catImage);
 
     const footer = document.createElement('div');
     footer.className = 'popup-footer';
 
     const description = document.createElement('div');
-    description.innerText = `Photo #${id}. Taken on ${date} in ${location}`;
+    if (caption) {
+        const captionElement = document.createElement('p');
+        captionElement.innerText = caption;
+        description.appendChild(captionElement);
+    }
+
+    const details = document.createElement('a');
+    details.href = `/photos/${id}`;
+    details.innerText = `Photo #${id}`;
+    description.appendChild(details);
+    description.appendChild(document.createTextNode(`. Taken on ${date} in ${location}`));
+
+    if (catNames.length > 0) {
+        description.appendChild(document.createElement('br'));
+        description.appendChild(document.createTextNode(`With ${catNames.join(', ')}`));
+    }
+
+    if (tags.length > 0) {
+        description.appendChild(document.createElement('br'));
+        tags.forEach(tag => {
+            const link = document.createElement('a');
+            link.href = `/search?tag=${encodeURIComponent(tag)}`;
+            link.innerText = `#${tag} `;
+            description.appendChild(link);
+        });
+    }
+
     footer.appendChild(description);
 
     const favButton = makeFavoriteButton(sha256);
@@ -40,17 +66,30 @@ function renderPopup(image, map) {
 
 }
 
-function makeImageLink(href, src, alt) {
+function makeImageLink(imageId, src, alt) {
     const img = document.createElement('img');
     img.src = src;
     img.alt = alt;
 
     const a = document.createElement('a');
-    a.href = href;
+    a.href = `?photo=${imageId}`;
+    a.onclick = (event) => {
+        event.preventDefault();
+        openLightbox(imageId);
+    };
     a.appendChild(img);
     return a;
 }
 
+// Opens the Leptos lightbox by changing the URL without a page load. The router only listens for
+// popstate, so tell it about the change.
+function openLightbox(imageId) {
+    const params = new URLSearchParams(window.location.search);
+    params.set('photo', imageId);
+    history.pushState(null, '', `${window.location.pathname}?${params}`);
+    window.dispatchEvent(new PopStateEvent('popstate'));
+}
+
 function makeFavoriteButton(imageHash) {
     const favs = new Favorites();
     const icon = favs.iconForStatus(imageHash);
@@ -105,23 +144,31 @@ function formatLocation(image) {
     return city ? `${city}, ${country}` : country
 }
 
+// Keep in sync with src/favorites_store.rs. Version 1 was a plain array of hashes.
 function Favorites() {
-    const load = () => new Set(JSON.parse(localStorage.getItem('favorites')));
-    const store = (s) => localStorage.setItem('favorites', JSON.stringify([...s]));
+    const load = () => {
+        const stored = JSON.parse(localStorage.getItem('favorites'));
+        if (Array.isArray(stored)) {
+            return {version: 2, folders: [], items: stored.map(sha256 => ({sha256, note: ''}))};
+        }
+        return stored || {version: 2, folders: [], items: []};
+    };
+    const store = (favs) => localStorage.setItem('favorites', JSON.stringify(favs));
+    const has = (favs, imageHash) => favs.items.some(item => item.sha256 === imageHash);
 
     return {
-        has: (imageHash) => load().has(imageHash),
-        read: () => [...load()],
-        iconForStatus: (imageHash) => load().has(imageHash) ? 'favorite-filled.svg' : 'favorite.svg',
+        has: (imageHash) => has(load(), imageHash),
+        read: () => load().items.map(item => item.sha256),
+        iconForStatus: (imageHash) => has(load(), imageHash) ? '/favorite-filled.svg' : '/favorite.svg',
 
         toggle: (imageHash) => {
-            let s = load();
-            if (s.has(imageHash)) {
-                s.delete(imageHash);
+            let favs = load();
+            if (has(favs, imageHash)) {
+                favs.items = favs.items.filter(item => item.sha256 !== imageHash);
             } else {
-                s.add(`${imageHash}`);
+                favs.items.push({sha256: `${imageHash}`, note: ''});
             }
-            store(s);
+            store(favs);
         },
     }
 }
@@ -0,0 +1,275 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use leptos_meta::*;
+use serde::{Deserialize, Serialize};
+use web_sys::{DragEvent, FileList, HtmlInputElement};
+
+use crate::api::Image;
+use crate::map::{create_map, format_location};
+
+#[allow(unused)] // unused in server-side binary
+const UPLOAD_URL: &str = "/admin/api/upload";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UploadResult {
+    #[serde(rename="fileName")]
+    pub file_name: String,
+    pub outcome: UploadOutcome,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag="status", rename_all="camelCase")]
+pub enum UploadOutcome {
+    Added { image: Box<Image> },
+    Duplicate { id: usize },
+    Failed { reason: String },
+}
+
+/// Places a photo that had no GPS data in its EXIF on the map and publishes it.
+#[server(SetLocation, "/admin/api")]
+pub async fn set_location(id: usize, latitude: f64, longitude: f64) -> Result<Image, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+    // before asking the geocoder about it
+    crate::location_editor::check_coordinates(latitude, longitude)?;
+
+    let mut image = state.catalog.get(id)
+        .ok_or_else(|| ServerFnError::ServerError(format!("no image with id {id}")))?;
+
+    crate::ingest::locate(&mut image, latitude, longitude).await;
+
+    crate::location_editor::store_location(&state, &user, id, (&image).into())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    use axum::extract::{Multipart, State};
+    use axum::Json;
+    use crate::ingest::{ingest, IngestError};
+    use crate::state::AppState;
+
+    /// Accepts a multipart form with one or more photos and runs each of them through the
+    /// ingest pipeline. A failing file doesn't stop the others from being processed.
+    pub async fn upload_handler(
+        State(state): State<AppState>,
+        mut multipart: Multipart,
+    ) -> Json<Vec<UploadResult>> {
+        let mut results = vec![];
+
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => {
+                    results.push(UploadResult {
+                        file_name: String::new(),
+                        outcome: UploadOutcome::Failed { reason: e.to_string() },
+                    });
+                    break;
+                }
+            };
+
+            let file_name = field.file_name().unwrap_or_default().to_string();
+
+            let outcome = match field.bytes().await {
+                Ok(data) => match ingest(&state.catalog, &state.media_dir, data.to_vec()).await {
+                    Ok(image) => UploadOutcome::Added { image: Box::new(image) },
+                    Err(IngestError::Duplicate(id)) => UploadOutcome::Duplicate { id },
+                    Err(e) => UploadOutcome::Failed { reason: e.to_string() },
+                },
+                Err(e) => UploadOutcome::Failed { reason: e.to_string() },
+            };
+
+            results.push(UploadResult { file_name, outcome });
+        }
+
+        Json(results)
+    }
+}}
+
+#[component]
+pub fn AdminNav() -> impl IntoView {
+    view! {
+        <nav>
+            <ul>
+                <li><a href="/admin/upload">"Upload"</a></li>
+                <li><a href="/admin/locations">"Locations"</a></li>
+                <li><a href="/admin/duplicates">"Duplicates"</a></li>
+                <li><a href="/admin/photos">"Photos"</a></li>
+                <li><a href="/admin/cats">"Cats"</a></li>
+            </ul>
+        </nav>
+    }
+}
+
+#[component]
+pub fn AdminUpload() -> impl IntoView {
+    let results = create_rw_signal(Vec::<UploadResult>::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let (uploading, set_uploading) = create_signal(false);
+
+    let upload = move |files: FileList| {
+        set_uploading(true);
+        spawn_local(async move {
+            match upload_files(files).await {
+                Ok(uploaded) => {
+                    set_error(None);
+                    results.update(|results| results.extend(uploaded));
+                }
+                Err(e) => set_error(Some(e)),
+            }
+            set_uploading(false);
+        });
+    };
+
+    view! {
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
+
+        <AdminNav/>
+        <h1>"Upload photos"</h1>
+        <p>
+            "Photos without GPS data can also be placed later in the "
+            <a href="/admin/locations">"location editor"</a>"."
+        </p>
+        <div
+            class="drop-zone"
+            on:dragover=|ev: DragEvent| ev.prevent_default()
+            on:drop=move |ev: DragEvent| {
+                ev.prevent_default();
+                if let Some(files) = ev.data_transfer().and_then(|dt| dt.files()) {
+                    upload(files);
+                }
+            }
+        >
+            <p>"Drop photos here or pick them:"</p>
+            <input
+                type="file"
+                accept="image/*"
+                multiple
+                on:change=move |ev| {
+                    let input: HtmlInputElement = event_target(&ev);
+                    if let Some(files) = input.files() {
+                        upload(files);
+                    }
+                }
+            />
+        </div>
+
+        <Show when=uploading fallback=|| ()>
+            <progress></progress>
+        </Show>
+        {move || error().map(|e| view! { <p class="upload-error">{e}</p> })}
+
+        <table>
+            <For
+                each=move || results().into_iter().enumerate()
+                key=|(index, _)| *index
+                children=move |(_, result)| view! { <UploadRow result/> }
+            />
+        </table>
+    }
+}
+
+#[component]
+fn UploadRow(result: UploadResult) -> impl IntoView {
+    let status = match result.outcome {
+        UploadOutcome::Added { image } if image.needs_location => view! {
+            <p>"Added as #"{image.id}", but it has no GPS data. Click on the map to place it."</p>
+            <LocationPicker image_id=image.id/>
+        }.into_view(),
+        UploadOutcome::Added { image } => view! {
+            <p>"Added as #"{image.id}" in "{format_location(&image)}</p>
+        }.into_view(),
+        UploadOutcome::Duplicate { id } => view! {
+            <p>"Already in the catalog as #"{id}</p>
+        }.into_view(),
+        UploadOutcome::Failed { reason } => view! {
+            <p class="upload-error">{reason}</p>
+        }.into_view(),
+    };
+
+    view! {
+        <tr>
+            <td>{result.file_name}</td>
+            <td>{status}</td>
+        </tr>
+    }
+}
+
+#[component]
+fn LocationPicker(image_id: usize) -> impl IntoView {
+    let element_id = format!("location-picker-{image_id}");
+    let set_location = create_server_action::<SetLocation>();
+
+    let map = {
+        let element_id = element_id.clone();
+        create_local_resource(
+            || (),
+            move |_| {
+                let element_id = element_id.clone();
+                async move { create_map(&element_id).await }
+            })
+    };
+
+    create_effect(move |_| {
+        if let Some(map) = map() {
+            let pin_map = map.clone();
+            let mut pin = None;
+            map.on_click(move |latitude, longitude| {
+                match &pin {
+                    Some(pin) => pin_map.move_pin(pin, latitude, longitude),
+                    None => pin = Some(pin_map.add_pin(latitude, longitude)),
+                }
+                set_location.dispatch(SetLocation { id: image_id, latitude, longitude });
+            });
+        }
+    });
+
+    on_cleanup(move || {
+        if let Some(map) = map() {
+            map.remove();
+        }
+    });
+
+    let status = move || match set_location.value().get() {
+        Some(Ok(image)) => format!("Placed in {}", format_location(&image)),
+        Some(Err(e)) => e.to_string(),
+        None => String::new(),
+    };
+
+    view! {
+        <div id=element_id class="location-picker"></div>
+        <small>{status}</small>
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn upload_files(_files: FileList) -> Result<Vec<UploadResult>, String> {
+    Ok(vec![])
+}
+
+#[cfg(not(feature = "ssr"))]
+async fn upload_files(files: FileList) -> Result<Vec<UploadResult>, String> {
+    let form = web_sys::FormData::new().map_err(|e| format!("{e:?}"))?;
+
+    for i in 0..files.length() {
+        if let Some(file) = files.get(i) {
+            form.append_with_blob_and_filename("photos", &file, &file.name())
+                .map_err(|e| format!("{e:?}"))?;
+        }
+    }
+
+    let response = gloo_net::http::Request::post(UPLOAD_URL)
+        .body(form)
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.ok() {
+        return Err(format!("upload failed: {} {}", response.status(), response.status_text()));
+    }
+
+    response.json::<Vec<UploadResult>>().await.map_err(|e| e.to_string())
+}
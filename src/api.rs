@@ -2,9 +2,9 @@ use leptos::Resource;
 use serde::{Deserialize, Serialize};
 
 #[allow(unused)] // unused in server-side binary
-const URL: &str = "https://catsof.asia/images";
+const URL: &str = "/images";
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Image {
     pub id: usize,
     #[serde(rename="urlLarge")]
@@ -14,11 +14,53 @@ pub struct Image {
     #[serde(rename="urlSmall")]
     pub url_small: String,
     pub sha256: String,
+    // 64 bit difference hash as hex, to find resized or re-encoded copies of the same photo
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub dhash: Option<String>,
     pub timestamp: String,
     pub latitude: f64,
     pub longitude: f64,
     pub city: String,
     pub country: String,
+    // set for uploads without GPS data, which aren't published until placed on the map
+    #[serde(rename="needsLocation", default, skip_serializing_if="std::ops::Not::not")]
+    pub needs_location: bool,
+    // publish only a coarse location, e.g. for photos taken at someone's home
+    #[serde(rename="hideLocation", default, skip_serializing_if="std::ops::Not::not")]
+    pub hide_location: bool,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub caption: Option<String>,
+    // the cats that can be seen in the photo
+    #[serde(rename="catIds", default, skip_serializing_if="Vec::is_empty")]
+    pub cat_ids: Vec<usize>,
+}
+
+/// A cat that was photographed more than once and got a name.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Cat {
+    pub id: usize,
+    pub name: String,
+    pub slug: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Cat {
+    /// Turns a cat's name into the identifier used in URLs, e.g. "Mr. Whiskers" -> "mr-whiskers".
+    pub fn slugify(name: &str) -> String {
+        slugify(name)
+    }
+}
+
+/// Turns a name into an identifier for URLs, e.g. "Chiang Mai" -> "chiang-mai".
+pub fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
 }
 
 #[derive(Copy, Clone)]
@@ -29,6 +71,14 @@ pub async fn fetch_images() -> Vec<Image> {
     vec![]
 }
 
+/// Serves the published part of the catalog at [`URL`].
+#[cfg(feature = "ssr")]
+pub async fn images_handler(
+    axum::extract::State(state): axum::extract::State<crate::state::AppState>,
+) -> axum::Json<Vec<Image>> {
+    axum::Json(state.public_images())
+}
+
 #[cfg(not(feature = "ssr"))]
 pub async fn fetch_images() -> Vec<Image> {
     let abort_controller = web_sys::AbortController::new().ok();
@@ -65,4 +115,32 @@ pub async fn fetch_images() -> Vec<Image> {
     }
     
     images.unwrap()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugifies_names() {
+        assert_eq!(Cat::slugify("Mr. Whiskers"), "mr-whiskers");
+        assert_eq!(Cat::slugify("  Tom  "), "tom");
+        assert_eq!(Cat::slugify("Chiang Mai"), "chiang-mai");
+        assert_eq!(Cat::slugify("Ho Chi Minh City!"), "ho-chi-minh-city");
+        assert_eq!(Cat::slugify("Kitty #2"), "kitty-2");
+        assert_eq!(Cat::slugify("Café Noir"), "café-noir");
+    }
+
+    #[test]
+    fn slugifies_spellings_alike() {
+        assert_eq!(Cat::slugify("mr whiskers"), Cat::slugify("Mr. Whiskers"));
+        assert_eq!(Cat::slugify("MR--WHISKERS"), Cat::slugify("Mr. Whiskers"));
+    }
+
+    #[test]
+    fn slugifies_punctuation_to_nothing() {
+        assert_eq!(Cat::slugify(""), "");
+        assert_eq!(Cat::slugify("!!!"), "");
+        assert_eq!(Cat::slugify(" - "), "");
+    }
+}
@@ -6,6 +6,19 @@ use crate::error_template::{AppError, ErrorTemplate};
 use crate::api::{ImagesResource, fetch_images};
 use crate::map::MapView;
 use crate::favorites::Favorites;
+use crate::admin::AdminUpload;
+use crate::location_editor::AdminLocations;
+use crate::duplicates::AdminDuplicates;
+use crate::photo_editor::AdminPhotos;
+use crate::search::Search;
+use crate::cats::{AdminCats, CatsResource, fetch_cats};
+use crate::photo::PhotoPage;
+use crate::cat_profile::CatProfilePage;
+use crate::gallery::Gallery;
+use crate::places::{City, Places};
+use crate::stats::StatsPage;
+use crate::collections::CollectionPage;
+use crate::social::DeepLinkMeta;
 
 #[component]
 pub fn App() -> impl IntoView {
@@ -19,13 +32,26 @@ pub fn App() -> impl IntoView {
     );
 
     provide_context(ImagesResource(images));
-    
+
+    let cats = create_local_resource(
+        || (),
+        |_| async move {
+            fetch_cats().await
+        },
+    );
+
+    provide_context(CatsResource(cats));
+
+    #[cfg(feature = "ssr")]
+    crate::security::provide_page_policy();
+
     view! {
         <Title text="Cats of Asia"/>
         <Stylesheet id="leptos" href="/pkg/cats-of-asia.css"/>
-        <Link rel="icon" href="apple-touch-icon.png"/>
-        <Link rel="apple-touch-startup-image" href="apple-touch-icon.png"/>
-        <Link rel="stylesheet" href="pico.min.css"/>
+        <Link rel="icon" href="/apple-touch-icon.png"/>
+        <Link rel="apple-touch-startup-image" href="/apple-touch-icon.png"/>
+        <Link rel="stylesheet" href="/pico.min.css"/>
+        <Link rel="alternate" type_="application/atom+xml" title="Newest cats" href="/feed.atom"/>
 
         <Router fallback=|| {
             let mut outside_errors = Errors::default();
@@ -34,9 +60,23 @@ pub fn App() -> impl IntoView {
         }>
             <main class="container-fluid">
                 <NavBar/>
+                <DeepLinkMeta/>
                 <Routes>
                     <Route path="/" view=MapView/>
                     <Route path="/favorites" view=Favorites/>
+                    <Route path="/gallery" view=Gallery/>
+                    <Route path="/places" view=Places ssr=SsrMode::Async/>
+                    <Route path="/places/:country/:city" view=City ssr=SsrMode::Async/>
+                    <Route path="/stats" view=StatsPage ssr=SsrMode::Async/>
+                    <Route path="/search" view=Search/>
+                    <Route path="/photos/:id" view=PhotoPage/>
+                    <Route path="/cats/by-name/:slug" view=CatProfilePage/>
+                    <Route path="/collections/:id" view=CollectionPage ssr=SsrMode::Async/>
+                    <Route path="/admin/upload" view=AdminUpload/>
+                    <Route path="/admin/locations" view=AdminLocations/>
+                    <Route path="/admin/duplicates" view=AdminDuplicates/>
+                    <Route path="/admin/photos" view=AdminPhotos/>
+                    <Route path="/admin/cats" view=AdminCats/>
                 </Routes>
             </main>
         </Router>
@@ -54,9 +94,21 @@ fn NavBar() -> impl IntoView {
                 <li>
                     <a href="/">Map</a>
                 </li>
+                <li>
+                    <a href="/gallery">Gallery</a>
+                </li>
+                <li>
+                    <a href="/places">Places</a>
+                </li>
                 <li>
                     <a href="/favorites">Favorites</a>
                 </li>
+                <li>
+                    <a href="/stats">Stats</a>
+                </li>
+                <li>
+                    <a href="/search">Search</a>
+                </li>
             </ul>
         </nav>
     }
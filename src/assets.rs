@@ -0,0 +1,217 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use flate2::write::GzEncoder;
+
+use crate::catalog::write_atomically;
+
+/// Files worth compressing ahead of time, the images are compressed already.
+const COMPRESSIBLE: [&str; 3] = ["js", "wasm", "css"];
+
+/// Headers a 304 keeps from the response it stands in for.
+const NOT_MODIFIED_HEADERS: [header::HeaderName; 4] =
+    [header::CACHE_CONTROL, header::ETAG, header::LAST_MODIFIED, header::VARY];
+
+/// How long browsers may use a file without asking the server again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CachePolicy {
+    /// For files whose name changes with their content, like the image variants which are named
+    /// after the hash of the upload.
+    Immutable,
+    /// For files that keep their name across deploys, like the `pkg/` bundle and `public/`. Each
+    /// use is revalidated, which is cheap with the 304s.
+    Revalidate,
+}
+
+impl CachePolicy {
+    fn header_value(self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            CachePolicy::Immutable => "public, max-age=31536000, immutable",
+            CachePolicy::Revalidate => "no-cache",
+        })
+    }
+}
+
+/// A weak ETag from what `ServeDir` tells about the file, its size and modification time. Ranges
+/// of a file get the ETag of the whole file.
+fn etag(headers: &HeaderMap) -> Option<HeaderValue> {
+    let length: u64 = match headers.get(header::CONTENT_RANGE) {
+        Some(range) => range.to_str().ok()?.rsplit('/').next()?.parse().ok()?,
+        None => headers.get(header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()?,
+    };
+    let modified = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    let modified = httpdate::parse_http_date(modified).ok()?
+        .duration_since(std::time::UNIX_EPOCH).ok()?
+        .as_secs();
+    // compressed variants of a file differ in length
+    let encoding = headers.get(header::CONTENT_ENCODING)
+        .and_then(|encoding| encoding.to_str().ok())
+        .map(|encoding| format!("-{encoding}"))
+        .unwrap_or_default();
+
+    HeaderValue::from_str(&format!("W/\"{length:x}-{modified:x}{encoding}\"")).ok()
+}
+
+/// Whether an `If-None-Match` header lists `etag`, using the weak comparison.
+fn matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default().trim_start_matches("W/");
+
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Adds `Cache-Control` and an `ETag` to files served by `ServeDir`, and answers `If-None-Match`
+/// with a 304. `ServeDir` itself already handles `If-Modified-Since` and ranges.
+pub async fn cache_headers<B>(
+    State(policy): State<CachePolicy>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    if if_none_match.is_some() {
+        // RFC 9110 section 13.1.3: the ETag decides if both are sent
+        request.headers_mut().remove(header::IF_MODIFIED_SINCE);
+    }
+
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if status != StatusCode::OK && status != StatusCode::PARTIAL_CONTENT && status != StatusCode::NOT_MODIFIED {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, policy.header_value());
+    if policy == CachePolicy::Revalidate {
+        headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    let Some(etag) = etag(headers) else {
+        return response;
+    };
+    headers.insert(header::ETAG, etag.clone());
+
+    match if_none_match {
+        Some(if_none_match) if status == StatusCode::OK && matches(&if_none_match, &etag) => {
+            let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+            for name in NOT_MODIFIED_HEADERS {
+                if let Some(value) = response.headers().get(&name) {
+                    not_modified.headers_mut().insert(name, value.clone());
+                }
+            }
+            not_modified
+        }
+        _ => response,
+    }
+}
+
+/// Writes gzip and brotli versions next to the scripts, WebAssembly and stylesheets under `root`,
+/// for `ServeDir` to send to browsers that accept them. Files that are up to date are skipped, so
+/// this only does work after a deploy. Returns the number of files compressed.
+pub fn precompress(root: &Path) -> io::Result<usize> {
+    let mut compressed = 0;
+
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            compressed += precompress(&path)?;
+            continue;
+        }
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        if !COMPRESSIBLE.contains(&extension) {
+            continue;
+        }
+
+        let modified = fs::metadata(&path)?.modified()?;
+        let is_stale = |suffix: &str| {
+            let mut compressed = path.as_os_str().to_owned();
+            compressed.push(suffix);
+            fs::metadata(compressed)
+                .and_then(|meta| meta.modified())
+                .map_or(true, |compressed| compressed < modified)
+        };
+        if !is_stale(".gz") && !is_stale(".br") {
+            continue;
+        }
+
+        let data = fs::read(&path)?;
+
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gzip.write_all(&data)?;
+        write_atomically(&path.with_extension(format!("{extension}.gz")), &gzip.finish()?)?;
+
+        let mut brotli = Vec::new();
+        let params = brotli::enc::BrotliEncoderParams { quality: 11, ..Default::default() };
+        brotli::BrotliCompress(&mut data.as_slice(), &mut brotli, &params)?;
+        write_atomically(&path.with_extension(format!("{extension}.br")), &brotli)?;
+
+        compressed += 1;
+    }
+
+    Ok(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODIFIED: &str = "Sun, 05 Nov 2023 14:30:00 GMT";
+
+    fn headers(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_static(value))).collect()
+    }
+
+    #[test]
+    fn tags_files_by_length_and_modification_time() {
+        let file = headers(&[(header::CONTENT_LENGTH, "1000"), (header::LAST_MODIFIED, MODIFIED)]);
+        assert_eq!(etag(&file).unwrap(), "W/\"3e8-6547a6e8\"");
+
+        let gzipped = headers(&[
+            (header::CONTENT_LENGTH, "400"),
+            (header::LAST_MODIFIED, MODIFIED),
+            (header::CONTENT_ENCODING, "gzip"),
+        ]);
+        assert_eq!(etag(&gzipped).unwrap(), "W/\"190-6547a6e8-gzip\"");
+    }
+
+    #[test]
+    fn tags_ranges_like_the_whole_file() {
+        let range = headers(&[
+            (header::CONTENT_LENGTH, "100"),
+            (header::CONTENT_RANGE, "bytes 0-99/1000"),
+            (header::LAST_MODIFIED, MODIFIED),
+        ]);
+        assert_eq!(etag(&range).unwrap(), "W/\"3e8-6547a6e8\"");
+    }
+
+    #[test]
+    fn tags_nothing_without_a_modification_time() {
+        assert_eq!(etag(&headers(&[(header::CONTENT_LENGTH, "1000")])), None);
+        let unknown_length = headers(&[(header::CONTENT_RANGE, "bytes 0-99/*"), (header::LAST_MODIFIED, MODIFIED)]);
+        assert_eq!(etag(&unknown_length), None);
+    }
+
+    #[test]
+    fn compares_etags_weakly() {
+        let etag = HeaderValue::from_static("W/\"3e8-6547a6e8\"");
+        let matches = |if_none_match| matches(&HeaderValue::from_static(if_none_match), &etag);
+
+        assert!(matches("W/\"3e8-6547a6e8\""));
+        assert!(matches("\"3e8-6547a6e8\""));
+        assert!(matches("\"other\", W/\"3e8-6547a6e8\""));
+        assert!(matches("*"));
+        assert!(!matches("W/\"3e8-6547a6e9\""));
+        assert!(!matches("W/\"3e8-6547a6e8-gzip\""));
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::ingest::TIMESTAMP_FORMAT;
+
+/// An append-only log of the changes admins make to the catalog, one JSON object per line.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a, B: Serialize, A: Serialize> {
+    timestamp: String,
+    user: &'a str,
+    #[serde(rename = "imageId")]
+    image_id: usize,
+    action: &'a str,
+    before: B,
+    after: A,
+}
+
+impl AuditLog {
+    pub fn new(path: impl AsRef<Path>) -> AuditLog {
+        AuditLog {
+            path: path.as_ref().to_path_buf(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn record(
+        &self,
+        user: &str,
+        image_id: usize,
+        action: &str,
+        before: impl Serialize,
+        after: impl Serialize,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            timestamp: Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+            user,
+            image_id,
+            action,
+            before,
+            after,
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().expect("audit log lock poisoned");
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+}
@@ -0,0 +1,183 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use leptos::{use_context, ServerFnError};
+use sha2::{Digest, Sha256};
+
+use crate::rate_limit::{too_many_requests, ClientAddress};
+use crate::state::{use_app_state, AppState};
+
+const REALM: &str = "Basic realm=\"Cats of Asia admin\"";
+
+#[derive(Clone, Debug)]
+pub struct AdminCredentials {
+    username: String,
+    password: String,
+}
+
+impl AdminCredentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> AdminCredentials {
+        AdminCredentials {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Checks the HTTP basic auth credentials in `headers`.
+    pub fn authorize(&self, headers: &HeaderMap) -> bool {
+        let Some(encoded) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+        else {
+            return false;
+        };
+
+        let Some(decoded) = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        else {
+            return false;
+        };
+
+        match decoded.split_once(':') {
+            Some((username, password)) => {
+                // evaluate both so the response time doesn't tell which one was wrong
+                let username_ok = constant_time_eq(username, &self.username);
+                let password_ok = constant_time_eq(password, &self.password);
+                username_ok && password_ok
+            }
+            None => false,
+        }
+    }
+}
+
+/// Middleware that puts everything below `/admin` behind HTTP basic auth.
+///
+/// Without configured credentials the admin area is closed entirely. Addresses that fail to log
+/// in too often are turned away for a while, see [`RateLimiter::check_login`].
+pub async fn admin_guard<B>(
+    State(state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !is_admin_path(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let Some(admin) = &state.admin else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+
+    let address = state.rate_limiter.address(&state, &req);
+    if let Err(retry_after) = state.rate_limiter.check_login(&address).await {
+        return too_many_requests(retry_after);
+    }
+
+    if admin.authorize(req.headers()) {
+        next.run(req).await
+    } else {
+        // browsers ask without credentials first, that's no failed login
+        if req.headers().contains_key(header::AUTHORIZATION) {
+            state.rate_limiter.failed_login(&address).await;
+        }
+        unauthorized()
+    }
+}
+
+/// Fails unless the current server function request carries valid admin credentials, otherwise
+/// returns the admin's username.
+///
+/// Server functions can be called through any registered prefix, so admin server functions
+/// must call this themselves instead of relying on [`admin_guard`].
+pub async fn require_admin() -> Result<String, ServerFnError> {
+    let state = use_app_state()?;
+    let request = use_context::<leptos_axum::RequestParts>()
+        .ok_or_else(|| ServerFnError::ServerError("no request to authorize".into()))?;
+    let ClientAddress(address) = use_context::<ClientAddress>().unwrap_or(ClientAddress(String::new()));
+
+    if state.rate_limiter.check_login(&address).await.is_err() {
+        return Err(ServerFnError::ServerError("too many failed logins, try again later".into()));
+    }
+
+    match state.admin {
+        Some(admin) if admin.authorize(&request.headers) => Ok(admin.username().to_string()),
+        _ => {
+            state.rate_limiter.failed_login(&address).await;
+            Err(ServerFnError::ServerError("unauthorized".into()))
+        }
+    }
+}
+
+/// `/admin` and everything below it, but not e.g. `/administrator`.
+fn is_admin_path(path: &str) -> bool {
+    path == "/admin" || path.starts_with("/admin/")
+}
+
+fn unauthorized() -> Response {
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static(REALM));
+    response
+}
+
+/// Compares the digests of `a` and `b` rather than the strings themselves, so how long it takes
+/// doesn't depend on where they differ, or on their lengths.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    Sha256::digest(a)
+        .iter()
+        .zip(Sha256::digest(b).iter())
+        .fold(0, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_paths() {
+        assert!(is_admin_path("/admin"));
+        assert!(is_admin_path("/admin/upload"));
+        assert!(is_admin_path("/admin/api/upload"));
+        assert!(!is_admin_path("/administrator"));
+        assert!(!is_admin_path("/api/admin"));
+        assert!(!is_admin_path("/"));
+    }
+
+    #[test]
+    fn compares_strings_of_any_length() {
+        assert!(constant_time_eq("hunter2", "hunter2"));
+        assert!(constant_time_eq("", ""));
+        assert!(!constant_time_eq("hunter2", "hunter3"));
+        assert!(!constant_time_eq("hunter2", "hunter22"));
+        assert!(!constant_time_eq("hunter2", ""));
+    }
+
+    #[test]
+    fn authorizes_basic_auth() {
+        let admin = AdminCredentials::new("admin", "secret");
+        let header = |credentials: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("Basic {}", STANDARD.encode(credentials));
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&value).unwrap());
+            headers
+        };
+
+        assert!(admin.authorize(&header("admin:secret")));
+        assert!(!admin.authorize(&header("admin:wrong")));
+        assert!(!admin.authorize(&header("someone:secret")));
+        assert!(!admin.authorize(&header("admin")));
+        assert!(!admin.authorize(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,210 @@
+use leptos::*;
+use leptos_meta::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Cat, Image};
+use crate::error_template::{AppError, ErrorTemplate};
+use crate::lightbox::{use_lightbox_href, Lightbox};
+use crate::map::{create_map, format_location};
+
+const MAP_ELEMENT_ID: &str = "cat-map";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CatProfile {
+    pub cat: Cat,
+    /// Every published photo of the cat, oldest first.
+    pub sightings: Vec<Image>,
+}
+
+impl CatProfile {
+    pub fn first_seen(&self) -> Option<&str> {
+        self.sightings.first().map(|img| date(&img.timestamp))
+    }
+
+    pub fn last_seen(&self) -> Option<&str> {
+        self.sightings.last().map(|img| date(&img.timestamp))
+    }
+
+    /// The places the cat was seen in, in the order it was first seen there.
+    pub fn places(&self) -> Vec<String> {
+        let mut places: Vec<String> = vec![];
+        for place in self.sightings.iter().map(format_location) {
+            if !places.contains(&place) {
+                places.push(place);
+            }
+        }
+        places
+    }
+}
+
+fn date(timestamp: &str) -> &str {
+    timestamp.split('T').next().unwrap_or_default()
+}
+
+#[server(GetCatProfile, "/api")]
+pub async fn get_cat_profile(slug: String) -> Result<Option<CatProfile>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let Some(cat) = state.catalog.cat_by_slug(&slug) else {
+        return Ok(None);
+    };
+
+    let mut sightings: Vec<Image> = state.public_images()
+        .into_iter()
+        .filter(|img| img.cat_ids.contains(&cat.id))
+        .collect();
+    sightings.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+    Ok(Some(CatProfile { cat, sightings }))
+}
+
+#[derive(Params, Clone, Debug, PartialEq)]
+struct CatParams {
+    slug: String,
+}
+
+#[component]
+pub fn CatProfilePage() -> impl IntoView {
+    let params = use_params::<CatParams>();
+    let slug = move || params.with(|p| p.as_ref().map(|p| p.slug.clone()).ok());
+
+    let profile = create_resource(slug, |slug| async move {
+        match slug {
+            Some(slug) => get_cat_profile(slug).await,
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
+
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || profile.get().map(|profile| match profile {
+                Ok(Some(profile)) => view! { <Profile profile/> }.into_view(),
+                Ok(None) => {
+                    let mut outside_errors = Errors::default();
+                    outside_errors.insert_with_default_key(AppError::NotFound);
+                    view! { <ErrorTemplate outside_errors/> }.into_view()
+                }
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn Profile(profile: CatProfile) -> impl IntoView {
+    let seen = match (profile.first_seen(), profile.last_seen()) {
+        (Some(first), Some(last)) if first == last => format!("Seen once, on {first}."),
+        (Some(first), Some(last)) => {
+            format!("Seen {} times between {first} and {last}.", profile.sightings.len())
+        }
+        _ => "Not seen in any published photo yet.".to_string(),
+    };
+    let places = profile.places().join(" → ");
+    let has_sightings = !profile.sightings.is_empty();
+
+    let name = profile.cat.name.clone();
+    let lightbox_href = use_lightbox_href();
+    let sightings = profile.sightings.clone();
+    let photos = profile.sightings.clone().into_iter()
+        .map(|image| {
+            let href = lightbox_href(image.id);
+            let alt = format!("photo #{} showing {name}", image.id);
+            let caption = format!("{} in {}", date(&image.timestamp), format_location(&image));
+            view! {
+                <div class="fav-card">
+                    <article>
+                        <A href=href><img src=image.url_small alt=alt loading="lazy"/></A>
+                        <p>{caption}</p>
+                    </article>
+                </div>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <Title text=format!("{} - Cats of Asia", profile.cat.name)/>
+        <hgroup>
+            <h1>{profile.cat.name.clone()}</h1>
+            <p>{seen}</p>
+        </hgroup>
+        {profile.cat.description.clone().map(|description| view! { <p>{description}</p> })}
+        <Show when=move || has_sightings fallback=|| ()>
+            <p>"Seen in "{places.clone()}</p>
+        </Show>
+        {has_sightings.then(|| view! { <SightingMap profile=profile.clone()/> })}
+        <Lightbox images=Signal::derive(move || sightings.clone())/>
+        <div class="search-results">{photos}</div>
+    }
+}
+
+/// Shows the photos of a cat on a map, connected in the order they were taken.
+#[component]
+fn SightingMap(profile: CatProfile) -> impl IntoView {
+    let map = create_local_resource(
+        || (),
+        |_| create_map(MAP_ELEMENT_ID));
+
+    on_cleanup(move || {
+        if let Some(map) = map() {
+            map.remove();
+        }
+    });
+
+    create_effect(move |_| {
+        if let Some(map) = map() {
+            let names = [profile.cat.name.clone()];
+            let path: Vec<(f64, f64)> = profile.sightings.iter()
+                .map(|img| (img.latitude, img.longitude))
+                .collect();
+
+            map.fit_points(&path, 16);
+            map.add_path(&path);
+            profile.sightings.iter().for_each(|img| map.add_marker(img, &names, 12));
+        }
+    });
+
+    view! {
+        <div id=MAP_ELEMENT_ID class="cat-map"></div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sighting(timestamp: &str, city: &str) -> Image {
+        Image { timestamp: timestamp.into(), city: city.into(), country: "Thailand".into(), ..Default::default() }
+    }
+
+    #[test]
+    fn summarizes_sightings() {
+        let profile = CatProfile {
+            cat: Cat { id: 1, name: "Tom".into(), slug: "tom".into(), description: None },
+            sightings: vec![
+                sighting("2023-01-02T10:00:00", "Bangkok"),
+                sighting("2023-03-04T11:00:00", "Chiang Mai"),
+                sighting("2023-05-06T12:00:00", "Bangkok"),
+            ],
+        };
+
+        assert_eq!(profile.first_seen(), Some("2023-01-02"));
+        assert_eq!(profile.last_seen(), Some("2023-05-06"));
+        assert_eq!(profile.places(), ["Bangkok, Thailand", "Chiang Mai, Thailand"]);
+    }
+
+    #[test]
+    fn summarizes_no_sightings() {
+        let profile = CatProfile {
+            cat: Cat { id: 1, name: "Tom".into(), slug: "tom".into(), description: None },
+            sightings: vec![],
+        };
+
+        assert_eq!(profile.first_seen(), None);
+        assert!(profile.places().is_empty());
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::{Cat, Image};
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("failed to access catalog file: {0}")]
+    Io(#[from] io::Error),
+    #[error("catalog file is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("this photo is already in the catalog as #{0}")]
+    Duplicate(usize),
+    #[error("no image with id {0}")]
+    NotFound(usize),
+    #[error("no cat with id {0}")]
+    CatNotFound(usize),
+    #[error("\"{0}\" can't be a cat's name, it needs letters or digits")]
+    InvalidCatName(String),
+    #[error("catalog lock poisoned by a panic")]
+    Poisoned,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CatalogData {
+    images: Vec<Image>,
+    #[serde(default)]
+    cats: Vec<Cat>,
+    /// The id of the next image, ids of deleted images aren't reused so links keep pointing to
+    /// the same photo or to none.
+    #[serde(default)]
+    next_id: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCatalog {
+    Current(CatalogData),
+    // catalogs written before cats existed are a plain list of images
+    Images(Vec<Image>),
+}
+
+/// The image metadata of the whole site, kept in memory and persisted as a JSON file.
+///
+/// Every mutation rewrites the file by writing to a temporary file next to it and renaming that
+/// over the original, so a crash never leaves a half written catalog behind.
+#[derive(Clone, Debug)]
+pub struct Catalog {
+    path: PathBuf,
+    data: Arc<RwLock<CatalogData>>,
+}
+
+impl Catalog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Catalog, CatalogError> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut data = match fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data)? {
+                StoredCatalog::Current(data) => data,
+                StoredCatalog::Images(images) => CatalogData {
+                    images,
+                    ..CatalogData::default()
+                },
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CatalogData::default(),
+            Err(e) => return Err(e.into()),
+        };
+        // catalogs from before the counter continue after their highest id
+        let highest = data.images.iter().map(|img| img.id).max().unwrap_or(0);
+        data.next_id = data.next_id.max(highest + 1);
+
+        Ok(Catalog {
+            path,
+            data: Arc::new(RwLock::new(data)),
+        })
+    }
+
+    /// Checks that the catalog can still be read and saved, for the readiness probe.
+    pub fn check(&self) -> Result<(), CatalogError> {
+        if self.data.is_poisoned() {
+            return Err(CatalogError::Poisoned);
+        }
+
+        // saving writes a temporary file next to the catalog
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if fs::metadata(dir)?.permissions().readonly() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "catalog directory is read-only").into());
+        }
+        Ok(())
+    }
+
+    /// All images, including the ones that still need to be placed on the map.
+    pub fn images(&self) -> Vec<Image> {
+        self.data.read().expect("catalog lock poisoned").images.clone()
+    }
+
+    /// The images that can be shown to visitors.
+    pub fn published_images(&self) -> Vec<Image> {
+        self.data
+            .read()
+            .expect("catalog lock poisoned")
+            .images
+            .iter()
+            .filter(|img| !img.needs_location)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: usize) -> Option<Image> {
+        self.data
+            .read()
+            .expect("catalog lock poisoned")
+            .images
+            .iter()
+            .find(|img| img.id == id)
+            .cloned()
+    }
+
+    pub fn find_by_sha256(&self, sha256: &str) -> Option<Image> {
+        self.data
+            .read()
+            .expect("catalog lock poisoned")
+            .images
+            .iter()
+            .find(|img| img.sha256 == sha256)
+            .cloned()
+    }
+
+    /// Adds `image` to the catalog, assigning it an id no image had before. Fails if there is an
+    /// image with the same sha256 already.
+    pub fn insert(&self, mut image: Image) -> Result<Image, CatalogError> {
+        self.modify(|data| {
+            if let Some(existing) = data.images.iter().find(|img| img.sha256 == image.sha256) {
+                return Err(CatalogError::Duplicate(existing.id));
+            }
+
+            image.id = data.next_id;
+            data.next_id += 1;
+            data.images.push(image.clone());
+            Ok(image)
+        })
+    }
+
+    /// Applies `f` to the image with the given id and persists the result.
+    pub fn update(&self, id: usize, f: impl FnOnce(&mut Image)) -> Result<Image, CatalogError> {
+        self.modify(|data| {
+            let image = data
+                .images
+                .iter_mut()
+                .find(|img| img.id == id)
+                .ok_or(CatalogError::NotFound(id))?;
+
+            f(image);
+            Ok(image.clone())
+        })
+    }
+
+    pub fn remove(&self, id: usize) -> Result<Image, CatalogError> {
+        self.modify(|data| {
+            let index = data
+                .images
+                .iter()
+                .position(|img| img.id == id)
+                .ok_or(CatalogError::NotFound(id))?;
+
+            Ok(data.images.remove(index))
+        })
+    }
+
+    pub fn cats(&self) -> Vec<Cat> {
+        self.data.read().expect("catalog lock poisoned").cats.clone()
+    }
+
+    pub fn cat_by_slug(&self, slug: &str) -> Option<Cat> {
+        self.data
+            .read()
+            .expect("catalog lock poisoned")
+            .cats
+            .iter()
+            .find(|cat| cat.slug == slug)
+            .cloned()
+    }
+
+    /// Returns the cat whose name has the same slug as `name`, adding a new one if there is none.
+    pub fn find_or_insert_cat(&self, name: &str) -> Result<Cat, CatalogError> {
+        if let Some(cat) = self.cat_by_slug(&Cat::slugify(name)) {
+            return Ok(cat);
+        }
+        self.modify(|data| data.find_or_insert_cat(name))
+    }
+
+    /// Sets caption and tags of the image with the given id, and its cats by name, adding the ones
+    /// that don't exist yet. Nothing is saved if one of the names is invalid.
+    pub fn describe(
+        &self,
+        id: usize,
+        caption: Option<String>,
+        tags: Vec<String>,
+        cat_names: &[&str],
+    ) -> Result<Image, CatalogError> {
+        self.modify(|data| {
+            let mut cat_ids = cat_names.iter()
+                .map(|name| data.find_or_insert_cat(name).map(|cat| cat.id))
+                .collect::<Result<Vec<_>, _>>()?;
+            // different spellings of a name can end up at the same cat, keep the first
+            let mut seen = std::collections::HashSet::new();
+            cat_ids.retain(|id| seen.insert(*id));
+
+            let image = data
+                .images
+                .iter_mut()
+                .find(|img| img.id == id)
+                .ok_or(CatalogError::NotFound(id))?;
+
+            image.caption = caption;
+            image.tags = tags;
+            image.cat_ids = cat_ids;
+            Ok(image.clone())
+        })
+    }
+
+    /// Applies `f` to the cat with the given id and persists the result.
+    pub fn update_cat(&self, id: usize, f: impl FnOnce(&mut Cat)) -> Result<Cat, CatalogError> {
+        self.modify(|data| {
+            let cat = data
+                .cats
+                .iter_mut()
+                .find(|cat| cat.id == id)
+                .ok_or(CatalogError::CatNotFound(id))?;
+
+            f(cat);
+            Ok(cat.clone())
+        })
+    }
+
+    /// Applies `f` to a copy of the catalog, which replaces the catalog once it is saved. Nothing
+    /// changes if `f` or saving fails, so memory and file never disagree.
+    fn modify<T>(&self, f: impl FnOnce(&mut CatalogData) -> Result<T, CatalogError>) -> Result<T, CatalogError> {
+        let mut data = self.data.write().expect("catalog lock poisoned");
+        let mut changed = data.clone();
+
+        let result = f(&mut changed)?;
+        self.save(&changed)?;
+        *data = changed;
+        Ok(result)
+    }
+
+    fn save(&self, data: &CatalogData) -> Result<(), CatalogError> {
+        let data = serde_json::to_vec_pretty(data)?;
+        write_atomically(&self.path, &data)?;
+        Ok(())
+    }
+}
+
+impl CatalogData {
+    fn find_or_insert_cat(&mut self, name: &str) -> Result<Cat, CatalogError> {
+        let slug = Cat::slugify(name);
+        if slug.is_empty() {
+            return Err(CatalogError::InvalidCatName(name.to_string()));
+        }
+        if let Some(cat) = self.cats.iter().find(|cat| cat.slug == slug) {
+            return Ok(cat.clone());
+        }
+
+        let cat = Cat {
+            id: self.cats.iter().map(|cat| cat.id + 1).max().unwrap_or(1),
+            name: name.trim().to_string(),
+            slug,
+            description: None,
+        };
+        self.cats.push(cat.clone());
+        Ok(cat)
+    }
+}
+
+/// Replaces the file at `path` with `data`. The data is written to a temporary file next to it
+/// and synced before that is renamed over the original, so neither a crash nor a killed container
+/// leaves a half written file behind.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(parent)?;
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // makes the rename itself durable
+    fs::File::open(parent)?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A catalog in a file of its own, removed again when the test is done.
+    struct TempCatalog(Catalog);
+
+    impl TempCatalog {
+        fn new(name: &str) -> TempCatalog {
+            let path = std::env::temp_dir().join(format!("cats-of-asia-{}-{name}.json", std::process::id()));
+            let _ = fs::remove_file(&path);
+            TempCatalog(Catalog::open(path).unwrap())
+        }
+    }
+
+    impl Drop for TempCatalog {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0.path);
+        }
+    }
+
+    #[test]
+    fn finds_cats_by_slug() {
+        let catalog = TempCatalog::new("finds-cats");
+
+        let cat = catalog.0.find_or_insert_cat(" Mr. Whiskers ").unwrap();
+        assert_eq!((cat.name.as_str(), cat.slug.as_str()), ("Mr. Whiskers", "mr-whiskers"));
+        assert_eq!(catalog.0.find_or_insert_cat("mr whiskers").unwrap(), cat);
+        assert_eq!(catalog.0.cats().len(), 1);
+    }
+
+    #[test]
+    fn rejects_duplicate_photos() {
+        let catalog = TempCatalog::new("duplicates");
+        let image = Image { sha256: "abc".into(), ..Image::default() };
+
+        let first = catalog.0.insert(image.clone()).unwrap();
+        assert!(matches!(catalog.0.insert(image), Err(CatalogError::Duplicate(id)) if id == first.id));
+        assert_eq!(catalog.0.images().len(), 1);
+    }
+
+    #[test]
+    fn never_reuses_image_ids() {
+        let catalog = TempCatalog::new("image-ids");
+        let image = |sha256: &str| Image { sha256: sha256.into(), ..Image::default() };
+        let first = catalog.0.insert(image("a")).unwrap();
+        let second = catalog.0.insert(image("b")).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+
+        catalog.0.remove(second.id).unwrap();
+        assert_eq!(catalog.0.insert(image("b")).unwrap().id, 3);
+
+        let reopened = Catalog::open(&catalog.0.path).unwrap();
+        assert_eq!(reopened.insert(image("c")).unwrap().id, 4);
+    }
+
+    #[test]
+    fn continues_after_the_highest_id_of_old_catalogs() {
+        let catalog = TempCatalog::new("old-ids");
+        let images = vec![Image { id: 7, sha256: "a".into(), ..Image::default() }];
+        fs::write(&catalog.0.path, serde_json::to_vec(&images).unwrap()).unwrap();
+
+        let reopened = Catalog::open(&catalog.0.path).unwrap();
+        assert_eq!(reopened.insert(Image { sha256: "b".into(), ..Image::default() }).unwrap().id, 8);
+    }
+
+    #[test]
+    fn keeps_the_catalog_if_saving_fails() {
+        let catalog = TempCatalog::new("keeps-catalog");
+        let image = catalog.0.insert(Image::default()).unwrap();
+
+        // a file can't be created in another file
+        let broken = Catalog { path: catalog.0.path.join("catalog.json"), data: catalog.0.data.clone() };
+        assert!(broken.update(image.id, |img| img.caption = Some("lost".into())).is_err());
+        assert!(broken.remove(image.id).is_err());
+
+        assert_eq!(catalog.0.get(image.id).unwrap().caption, None);
+    }
+
+    #[test]
+    fn describes_with_new_and_existing_cats() {
+        let catalog = TempCatalog::new("describes");
+        let image = catalog.0.insert(Image::default()).unwrap();
+        let tom = catalog.0.find_or_insert_cat("Tom").unwrap();
+
+        let described = catalog.0.describe(image.id, None, vec!["orange".into()], &["tom", "Mimi", "TOM"]).unwrap();
+        let mimi = catalog.0.cat_by_slug("mimi").unwrap();
+        assert_eq!(described.cat_ids, [tom.id, mimi.id]);
+        assert_eq!(catalog.0.get(image.id).unwrap(), described);
+    }
+
+    #[test]
+    fn adds_no_cats_if_a_name_is_invalid() {
+        let catalog = TempCatalog::new("describes-invalid");
+        let image = catalog.0.insert(Image::default()).unwrap();
+
+        let result = catalog.0.describe(image.id, Some("lost".into()), vec![], &["Tom", "!!!"]);
+        assert!(matches!(result, Err(CatalogError::InvalidCatName(_))));
+        assert!(catalog.0.cats().is_empty());
+        assert_eq!(catalog.0.get(image.id).unwrap().caption, None);
+    }
+
+    #[test]
+    fn rejects_names_without_a_slug() {
+        let catalog = TempCatalog::new("rejects-names");
+
+        for name in ["", "!!!", " - "] {
+            assert!(matches!(catalog.0.find_or_insert_cat(name), Err(CatalogError::InvalidCatName(_))));
+        }
+        assert!(catalog.0.cats().is_empty());
+    }
+}
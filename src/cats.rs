@@ -0,0 +1,98 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::admin::AdminNav;
+use crate::api::Cat;
+
+#[derive(Copy, Clone)]
+pub struct CatsResource(pub Resource<(), Vec<Cat>>);
+
+#[server(ListCats, "/api")]
+pub async fn list_cats() -> Result<Vec<Cat>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+    Ok(state.catalog.cats())
+}
+
+pub async fn fetch_cats() -> Vec<Cat> {
+    list_cats().await.unwrap_or_else(|e| {
+        log::error!("failed to load cats: {e}");
+        vec![]
+    })
+}
+
+/// Looks up the names of the cats with the given ids.
+pub fn cat_names(cats: &[Cat], ids: &[usize]) -> Vec<String> {
+    cats.iter()
+        .filter(|cat| ids.contains(&cat.id))
+        .map(|cat| cat.name.clone())
+        .collect()
+}
+
+#[server(SaveCat, "/admin/api")]
+pub async fn save_cat(id: usize, name: String, description: String) -> Result<Cat, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    let slug = Cat::slugify(&name);
+    if slug.is_empty() {
+        return Err(ServerFnError::ServerError("a cat needs a name".into()));
+    }
+
+    if state.catalog.cat_by_slug(&slug).is_some_and(|other| other.id != id) {
+        return Err(ServerFnError::ServerError(format!("there already is a cat called {name}")));
+    }
+
+    let before = state.catalog.cats().into_iter().find(|cat| cat.id == id);
+    let description = Some(description.trim().to_string()).filter(|d| !d.is_empty());
+
+    let after = state.catalog
+        .update_cat(id, |cat| {
+            cat.name = name.trim().to_string();
+            cat.slug = slug;
+            cat.description = description;
+        })
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    state.audit
+        .record(&user, id, "save_cat", before, &after)
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    Ok(after)
+}
+
+#[component]
+pub fn AdminCats() -> impl IntoView {
+    let save = create_server_action::<SaveCat>();
+    let cats = create_resource(move || save.version().get(), |_| list_cats());
+
+    view! {
+        <AdminNav/>
+        <h1>"Cats"</h1>
+        <p>"Cats are added by naming them on the photos page."</p>
+        {move || save.value().get().and_then(Result::err).map(|e| view! {
+            <p class="upload-error">{e.to_string()}</p>
+        })}
+        <Transition fallback=|| view! { <progress></progress> }>
+            {move || cats.get().map(|cats| match cats {
+                Err(e) => view! { <p class="upload-error">{e.to_string()}</p> }.into_view(),
+                Ok(cats) => cats.into_iter()
+                    .map(|cat| view! { <CatForm cat save/> })
+                    .collect_view(),
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+fn CatForm(cat: Cat, save: Action<SaveCat, Result<Cat, ServerFnError>>) -> impl IntoView {
+    view! {
+        <ActionForm action=save>
+            <input type="hidden" name="id" value=cat.id/>
+            <div class="grid">
+                <input name="name" placeholder="Name" value=cat.name/>
+                <input name="description" placeholder="Description" value=cat.description/>
+                <input type="submit" value="Save"/>
+            </div>
+        </ActionForm>
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::auth::constant_time_eq;
+use crate::catalog::write_atomically;
+
+/// Random bytes in a collection id, too many to find collections by guessing.
+const ID_BYTES: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum CollectionError {
+    #[error("failed to access collections file: {0}")]
+    Io(#[from] io::Error),
+    #[error("collections file is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no collection with id {0}")]
+    NotFound(String),
+    #[error("wrong token for collection {0}")]
+    Forbidden(String),
+    #[error("no random numbers available: {0}")]
+    Random(#[from] getrandom::Error),
+}
+
+/// A list of photos someone published from their favorites.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredCollection {
+    pub id: String,
+    pub name: String,
+    /// The sha256 of the photos, in the order they were published in.
+    pub hashes: Vec<String>,
+    pub created: String,
+    pub updated: String,
+    /// Hash of the secret that is needed to change the collection. The secret itself only lives
+    /// in the publisher's browser.
+    token_hash: String,
+}
+
+/// The published collections, kept in memory and persisted as a JSON file the same way as the
+/// [`Catalog`](crate::catalog::Catalog).
+#[derive(Clone, Debug)]
+pub struct CollectionStore {
+    path: PathBuf,
+    data: Arc<RwLock<Vec<StoredCollection>>>,
+}
+
+impl CollectionStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<CollectionStore, CollectionError> {
+        let path = path.as_ref().to_path_buf();
+
+        let data = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(CollectionStore {
+            path,
+            data: Arc::new(RwLock::new(data)),
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<StoredCollection> {
+        self.data
+            .read()
+            .expect("collections lock poisoned")
+            .iter()
+            .find(|c| c.id == id)
+            .cloned()
+    }
+
+    pub fn all(&self) -> Vec<StoredCollection> {
+        self.data.read().expect("collections lock poisoned").clone()
+    }
+
+    /// Creates a new collection and returns it along with the token needed to change it.
+    pub fn create(
+        &self,
+        name: String,
+        hashes: Vec<String>,
+    ) -> Result<(StoredCollection, String), CollectionError> {
+        let mut data = self.data.write().expect("collections lock poisoned");
+
+        let token = random_hex(16)?;
+        let id = loop {
+            let id = random_hex(ID_BYTES)?;
+            if !data.iter().any(|c| c.id == id) {
+                break id;
+            }
+        };
+        let now = now();
+        let collection = StoredCollection {
+            id,
+            name,
+            hashes,
+            created: now.clone(),
+            updated: now,
+            token_hash: hash(&token),
+        };
+
+        data.push(collection.clone());
+        self.save(&data)?;
+        Ok((collection, token))
+    }
+
+    /// Replaces name and photos of a collection, if `token` is the one it was created with.
+    pub fn update(
+        &self,
+        id: &str,
+        token: &str,
+        name: String,
+        hashes: Vec<String>,
+    ) -> Result<StoredCollection, CollectionError> {
+        let mut data = self.data.write().expect("collections lock poisoned");
+        let collection = data
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| CollectionError::NotFound(id.to_string()))?;
+
+        if !constant_time_eq(&collection.token_hash, &hash(token)) {
+            return Err(CollectionError::Forbidden(id.to_string()));
+        }
+
+        collection.name = name;
+        collection.hashes = hashes;
+        collection.updated = now();
+
+        let collection = collection.clone();
+        self.save(&data)?;
+        Ok(collection)
+    }
+
+    fn save(&self, data: &[StoredCollection]) -> Result<(), CollectionError> {
+        let data = serde_json::to_vec_pretty(data)?;
+        write_atomically(&self.path, &data)?;
+        Ok(())
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().format(crate::ingest::TIMESTAMP_FORMAT).to_string()
+}
+
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// `bytes` random bytes from the operating system, as hex.
+fn random_hex(bytes: usize) -> Result<String, getrandom::Error> {
+    let mut buf = vec![0; bytes];
+    getrandom::getrandom(&mut buf)?;
+    Ok(buf.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store in a file of its own, removed again when the test is done.
+    struct TempStore(CollectionStore);
+
+    impl TempStore {
+        fn new(name: &str) -> TempStore {
+            let path = std::env::temp_dir().join(format!("cats-of-asia-{}-{name}.json", std::process::id()));
+            let _ = fs::remove_file(&path);
+            TempStore(CollectionStore::open(path).unwrap())
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0.path);
+        }
+    }
+
+    #[test]
+    fn creates_unique_ids() {
+        let store = TempStore::new("unique-ids");
+
+        let ids: Vec<String> = (0..20)
+            .map(|i| store.0.create(format!("Trip {i}"), vec![]).unwrap().0.id)
+            .collect();
+
+        assert!(ids.iter().all(|id| id.len() == ID_BYTES * 2));
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    #[test]
+    fn updates_with_the_token_only() {
+        let store = TempStore::new("token");
+        let (collection, token) = store.0.create("Bangkok".into(), vec!["a".into()]).unwrap();
+
+        assert!(matches!(
+            store.0.update(&collection.id, "wrong", "Mine now".into(), vec![]),
+            Err(CollectionError::Forbidden(_)),
+        ));
+        let updated = store.0.update(&collection.id, &token, "Bangkok trip".into(), vec!["b".into()]).unwrap();
+        assert_eq!(updated.name, "Bangkok trip");
+        assert_eq!(updated.hashes, ["b"]);
+
+        // and it's kept across restarts
+        let reopened = CollectionStore::open(&store.0.path).unwrap();
+        assert_eq!(reopened.get(&collection.id).unwrap().name, "Bangkok trip");
+    }
+}
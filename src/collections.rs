@@ -0,0 +1,208 @@
+use gloo_storage::{LocalStorage, Storage};
+use leptos::*;
+use leptos_meta::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::Image;
+use crate::error_template::{AppError, ErrorTemplate};
+use crate::favorites::load_favorites;
+use crate::gallery::Thumbnail;
+use crate::lightbox::Lightbox;
+use crate::map::PhotoMap;
+
+#[allow(unused)] // unused in client-side binary
+const MAX_NAME_LENGTH: usize = 100;
+#[allow(unused)] // unused in client-side binary
+const MAX_PHOTOS: usize = 500;
+
+const MAP_ELEMENT_ID: &str = "collection-map";
+
+/// Where the browser that published a collection keeps the token needed to update it.
+const PUBLISHED_KEY: &str = "published-collection";
+
+/// What the publisher of a collection needs to update it later.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PublishedCollection {
+    pub id: String,
+    pub token: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CollectionDetails {
+    pub name: String,
+    pub updated: String,
+    /// In the order they were published in.
+    pub images: Vec<Image>,
+}
+
+/// Publishes `hashes`, a comma-separated list of photo hashes. Creates a new collection, unless
+/// `id` and `token` of an earlier one are given, which is then replaced.
+#[server(PublishCollection, "/api")]
+pub async fn publish_collection(
+    name: String,
+    hashes: String,
+    id: Option<String>,
+    token: Option<String>,
+) -> Result<PublishedCollection, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ServerFnError::Args(format!("a name needs 1 to {MAX_NAME_LENGTH} characters")));
+    }
+
+    // only keep photos that exist, so nobody can store arbitrary data
+    let published = state.catalog.published_images();
+    let hashes: Vec<String> = hashes.split(',')
+        .filter(|hash| published.iter().any(|img| img.sha256 == *hash))
+        .map(str::to_string)
+        .collect();
+
+    if hashes.is_empty() || hashes.len() > MAX_PHOTOS {
+        return Err(ServerFnError::Args(format!("a collection needs 1 to {MAX_PHOTOS} photos")));
+    }
+
+    match (id, token) {
+        (Some(id), Some(token)) => {
+            let collection = state.collections
+                .update(&id, &token, name, hashes)
+                .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+            Ok(PublishedCollection { id: collection.id, token, name: collection.name })
+        }
+        _ => {
+            let (collection, token) = state.collections
+                .create(name, hashes)
+                .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+            Ok(PublishedCollection { id: collection.id, token, name: collection.name })
+        }
+    }
+}
+
+#[server(GetCollection, "/api")]
+pub async fn get_collection(id: String) -> Result<Option<CollectionDetails>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let Some(collection) = state.collections.get(&id) else {
+        return Ok(None);
+    };
+
+    let images = state.public_images();
+    let images = collection.hashes.iter()
+        .filter_map(|hash| images.iter().find(|img| &img.sha256 == hash).cloned())
+        .collect();
+
+    Ok(Some(CollectionDetails { name: collection.name, updated: collection.updated, images }))
+}
+
+#[derive(Params, Clone, Debug, PartialEq)]
+struct CollectionParams {
+    id: String,
+}
+
+#[component]
+pub fn CollectionPage() -> impl IntoView {
+    let params = use_params::<CollectionParams>();
+    let id = move || params.with(|p| p.as_ref().map(|p| p.id.clone()).ok());
+
+    let collection = create_resource(id, |id| async move {
+        match id {
+            Some(id) => get_collection(id).await,
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
+
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || collection.get().map(|collection| match collection {
+                Ok(Some(collection)) => view! { <Collection collection/> }.into_view(),
+                Ok(None) => {
+                    let mut outside_errors = Errors::default();
+                    outside_errors.insert_with_default_key(AppError::NotFound);
+                    view! { <ErrorTemplate outside_errors/> }.into_view()
+                }
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn Collection(collection: CollectionDetails) -> impl IntoView {
+    let CollectionDetails { name, updated, images } = collection;
+    let updated = updated.split('T').next().unwrap_or_default().to_string();
+    let photos = images.clone().into_iter()
+        .map(|image| view! { <Thumbnail image/> })
+        .collect_view();
+    let lightbox_images = images.clone();
+
+    view! {
+        <Title text=format!("{name} - Cats of Asia")/>
+        <hgroup>
+            <h1>{name}</h1>
+            <p>{images.len()}" photos, last changed on "{updated}</p>
+        </hgroup>
+        // the photos may all have been unpublished or deleted since
+        {(!images.is_empty()).then(|| view! { <PhotoMap element_id=MAP_ELEMENT_ID images/> })}
+        <Lightbox images=Signal::derive(move || lightbox_images.clone())/>
+        <div class="gallery">{photos}</div>
+    }
+}
+
+/// Publishes the local favorites as a collection, or updates the one published earlier from this
+/// browser.
+#[component]
+pub fn ShareCollection() -> impl IntoView {
+    let publish = create_server_action::<PublishCollection>();
+    let published = create_rw_signal(LocalStorage::get::<PublishedCollection>(PUBLISHED_KEY).ok());
+    let (name, set_name) = create_signal(
+        published.get_untracked().map(|p| p.name).unwrap_or_default());
+
+    create_effect(move |_| {
+        if let Some(Ok(collection)) = publish.value().get() {
+            LocalStorage::set(PUBLISHED_KEY, &collection).ok();
+            published.set(Some(collection));
+        }
+    });
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let previous = published.get_untracked();
+        publish.dispatch(PublishCollection {
+            name: name.get_untracked(),
+            hashes: load_favorites().join(","),
+            id: previous.as_ref().map(|p| p.id.clone()),
+            token: previous.map(|p| p.token),
+        });
+    };
+
+    view! {
+        <form on:submit=on_submit>
+            <div class="grid">
+                <input
+                    placeholder="Name your collection"
+                    required
+                    prop:value=name
+                    on:input=move |ev| set_name(event_target_value(&ev))
+                />
+                <input
+                    type="submit"
+                    disabled=publish.pending()
+                    value=move || if published().is_some() { "Update shared collection" } else { "Share as collection" }
+                />
+            </div>
+        </form>
+        {move || published().map(|collection| {
+            let href = format!("/collections/{}", collection.id);
+            view! { <p>"Shared at "<A href=href.clone()>{href}</A></p> }
+        })}
+        {move || publish.value().get().and_then(Result::err).map(|e| view! {
+            <p class="upload-error">{e.to_string()}</p>
+        })}
+    }
+}
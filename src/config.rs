@@ -0,0 +1,301 @@
+use std::path::PathBuf;
+
+use config::{Environment, File, FileFormat};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::auth::AdminCredentials;
+use crate::map::MapSettings;
+use crate::privacy::{LocationPrecision, PrivacySettings};
+use crate::rate_limit::RateLimitSettings;
+use crate::seo::RobotsSettings;
+use crate::telemetry::LogSettings;
+
+/// The file that is read if `COA_CONFIG` doesn't name another one. It's fine for it not to exist.
+const DEFAULT_PATH: &str = "cats-of-asia.toml";
+const ENV_PREFIX: &str = "COA";
+/// Shorter secrets for fuzzing locations could be guessed.
+const MIN_SECRET_LENGTH: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("couldn't load configuration: {0}")]
+    Load(#[from] config::ConfigError),
+    #[error("invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+/// Everything about the server that differs between installations. It's read from a TOML file,
+/// any of it can be overridden with `COA_*` environment variables, using `__` to get into
+/// sections, e.g. `COA_MAP__ACCESS_TOKEN`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub catalog_path: PathBuf,
+    pub audit_log_path: PathBuf,
+    pub collections_path: PathBuf,
+    pub media_dir: PathBuf,
+    /// Where visitors reach the site, e.g. `https://catsof.asia`, for absolute links in feeds,
+    /// sitemaps and link previews. Taken from the request headers if it isn't set.
+    pub public_url: Option<String>,
+    /// The server runs behind a reverse proxy that sets `X-Forwarded-For`, `-Host` and `-Proto`,
+    /// so they can be believed. Without a proxy clients could pick their own.
+    pub trust_proxy: bool,
+    pub map: MapSettings,
+    pub privacy: PrivacySettings,
+    /// The admin area is disabled without it.
+    pub admin: Option<AdminConfig>,
+    pub robots: RobotsSettings,
+    pub logging: LogSettings,
+    /// Seconds to wait for requests in flight on SIGTERM, less than the container runtime waits
+    /// before it kills the process.
+    pub shutdown_timeout: u64,
+    pub rate_limit: RateLimitSettings,
+}
+
+// defaults so a missing half is reported by `validate`, which knows where it is
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            catalog_path: "catalog.json".into(),
+            audit_log_path: "audit.jsonl".into(),
+            collections_path: "collections.json".into(),
+            media_dir: "media".into(),
+            public_url: None,
+            trust_proxy: false,
+            map: MapSettings::default(),
+            privacy: PrivacySettings::default(),
+            admin: None,
+            robots: RobotsSettings::default(),
+            logging: LogSettings::default(),
+            // Docker and Kubernetes wait 10 and 30 seconds
+            shutdown_timeout: 8,
+            rate_limit: RateLimitSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file named by `COA_CONFIG`, or [`DEFAULT_PATH`], and applies the environment on
+    /// top.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = std::env::var("COA_CONFIG").ok();
+        let file = match &path {
+            Some(path) => File::new(path, FileFormat::Toml).required(true),
+            None => File::new(DEFAULT_PATH, FileFormat::Toml).required(false),
+        };
+
+        let env = Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator("_")
+            .separator("__")
+            .try_parsing(true)
+            .list_separator(",")
+            .with_list_parse_key("map.center")
+            .with_list_parse_key("robots.disallow");
+
+        let mut builder = config::Config::builder();
+        // the groups have different defaults, which serde can't fill in field by field
+        let defaults = RateLimitSettings::default();
+        for (group, limit) in [
+            ("api", defaults.api),
+            ("upload", defaults.upload),
+            ("images", defaults.images),
+            ("admin_login", defaults.admin_login),
+        ] {
+            builder = builder
+                .set_default(format!("rate_limit.{group}.burst"), limit.burst)?
+                .set_default(format!("rate_limit.{group}.per_minute"), limit.per_minute)?;
+        }
+
+        let config: Config = builder
+            .add_source(file)
+            .add_source(env)
+            // the names from before there was a configuration file
+            .set_override_option("admin.username", std::env::var("COA_ADMIN_USER").ok())?
+            .set_override_option("admin.password", std::env::var("COA_ADMIN_PASSWORD").ok())?
+            .build()?
+            .try_deserialize()?;
+
+        config.validate()
+    }
+
+    /// Checks everything at once, so all mistakes can be fixed in one go.
+    fn validate(mut self) -> Result<Config, ConfigError> {
+        let mut errors = vec![];
+
+        for (key, path) in [
+            ("catalog_path", &self.catalog_path),
+            ("audit_log_path", &self.audit_log_path),
+            ("collections_path", &self.collections_path),
+            ("media_dir", &self.media_dir),
+        ] {
+            if path.as_os_str().is_empty() {
+                errors.push(format!("{key} must not be empty"));
+            }
+        }
+
+        if let Some(url) = &mut self.public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!("public_url must start with http:// or https://, got \"{url}\""));
+            }
+            // links are built by appending absolute paths
+            *url = url.trim_end_matches('/').to_string();
+        }
+
+        let map = &self.map;
+        for placeholder in ["{z}", "{x}", "{y}"] {
+            if !map.tile_url.contains(placeholder) {
+                errors.push(format!("map.tile_url must contain {placeholder}, got \"{}\"", map.tile_url));
+            }
+        }
+        if map.tile_url.contains("{accessToken}") && map.access_token.is_empty() {
+            errors.push("map.access_token is required by map.tile_url".to_string());
+        }
+        if map.tile_url.contains("{id}") && map.tile_id.is_empty() {
+            errors.push("map.tile_id is required by map.tile_url".to_string());
+        }
+        if map.zoom > map.max_zoom {
+            errors.push(format!("map.zoom must be at most map.max_zoom ({}), got {}", map.max_zoom, map.zoom));
+        }
+        let (latitude, longitude) = map.center;
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            errors.push(format!("map.center must be a latitude and a longitude, got [{latitude}, {longitude}]"));
+        }
+
+        match self.privacy.precision {
+            LocationPrecision::Snap { grid } if grid <= 0.0 => {
+                errors.push(format!("privacy.precision.grid must be positive, got {grid}"));
+            }
+            LocationPrecision::Fuzz { radius } if radius < 0.0 => {
+                errors.push(format!("privacy.precision.radius must not be negative, got {radius}"));
+            }
+            LocationPrecision::Fuzz { .. } if self.privacy.secret.len() < MIN_SECRET_LENGTH => {
+                errors.push(format!(
+                    "privacy.secret must be at least {MIN_SECRET_LENGTH} characters to fuzz locations",
+                ));
+            }
+            _ => {}
+        }
+        if self.privacy.hidden_grid <= 0.0 {
+            errors.push(format!("privacy.hidden_grid must be positive, got {}", self.privacy.hidden_grid));
+        }
+
+        if let Some(admin) = &self.admin {
+            if admin.username.is_empty() || admin.password.is_empty() {
+                errors.push("admin.username and admin.password must not be empty".to_string());
+            }
+        }
+
+        for path in &self.robots.disallow {
+            if !path.starts_with('/') {
+                errors.push(format!("robots.disallow paths must start with /, got \"{path}\""));
+            }
+        }
+
+        let rate_limit = &self.rate_limit;
+        for (group, limit) in [
+            ("api", rate_limit.api),
+            ("upload", rate_limit.upload),
+            ("images", rate_limit.images),
+            ("admin_login", rate_limit.admin_login),
+        ] {
+            if limit.burst == 0 || limit.per_minute == 0 {
+                errors.push(format!("rate_limit.{group}.burst and per_minute must be positive"));
+            }
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.filter) {
+            errors.push(format!("logging.filter is invalid: {e}"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(self),
+            false => Err(ConfigError::Invalid(errors)),
+        }
+    }
+
+    pub fn admin_credentials(&self) -> Option<AdminCredentials> {
+        self.admin.as_ref().map(|admin| AdminCredentials::new(&admin.username, &admin.password))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(config: Config) -> Vec<String> {
+        match config.validate() {
+            Ok(_) => vec![],
+            Err(ConfigError::Invalid(errors)) => errors,
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn accepts_the_defaults() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn trims_the_public_url() {
+        let config = Config { public_url: Some("https://catsof.asia/".into()), ..Config::default() };
+        assert_eq!(config.validate().unwrap().public_url.as_deref(), Some("https://catsof.asia"));
+    }
+
+    #[test]
+    fn reports_every_mistake() {
+        let mut config = Config {
+            media_dir: "".into(),
+            public_url: Some("catsof.asia".into()),
+            admin: Some(AdminConfig { username: "admin".into(), password: String::new() }),
+            ..Config::default()
+        };
+        config.map.zoom = 20;
+        config.robots.disallow = vec!["private".into()];
+
+        assert_eq!(errors(config), [
+            "media_dir must not be empty",
+            "public_url must start with http:// or https://, got \"catsof.asia\"",
+            "map.zoom must be at most map.max_zoom (19), got 20",
+            "admin.username and admin.password must not be empty",
+            "robots.disallow paths must start with /, got \"private\"",
+        ]);
+    }
+
+    #[test]
+    fn requires_tile_placeholders_and_their_values() {
+        let mut config = Config::default();
+        config.map.tile_url = "https://api.mapbox.com/{id}/{z}/{x}.png?access_token={accessToken}".into();
+
+        assert_eq!(errors(config), [
+            "map.tile_url must contain {y}, got \"https://api.mapbox.com/{id}/{z}/{x}.png?access_token={accessToken}\"",
+            "map.access_token is required by map.tile_url",
+            "map.tile_id is required by map.tile_url",
+        ]);
+    }
+
+    #[test]
+    fn requires_a_secret_to_fuzz() {
+        let mut config = Config::default();
+        config.privacy.precision = LocationPrecision::Fuzz { radius: 100.0 };
+        config.privacy.secret = "too short".into();
+        assert_eq!(errors(config.clone()), ["privacy.secret must be at least 16 characters to fuzz locations"]);
+
+        config.privacy.secret = "long enough to be a secret".into();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_rate_limits() {
+        let mut config = Config::default();
+        config.rate_limit.upload.per_minute = 0;
+        assert_eq!(errors(config), ["rate_limit.upload.burst and per_minute must be positive"]);
+    }
+}
@@ -0,0 +1,356 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::admin::AdminNav;
+use crate::api::Image;
+use crate::map::format_location;
+
+/// Hashes that differ in at most this many bits are considered the same photo.
+#[allow(unused)] // unused in client-side bundle
+const MAX_DISTANCE: u32 = 8;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DuplicateGroup {
+    pub images: Vec<Image>,
+    // the largest hamming distance between two hashes of the group
+    #[serde(rename="maxDistance")]
+    pub max_distance: u32,
+}
+
+#[server(FindDuplicates, "/admin/api")]
+pub async fn find_duplicates() -> Result<Vec<DuplicateGroup>, ServerFnError> {
+    crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    backfill_hashes(&state).await?;
+    Ok(group_duplicates(state.catalog.images()))
+}
+
+/// Keeps the image `keep` and deletes the images in `remove`. Metadata the kept image is missing,
+/// like a location or an earlier timestamp, is taken from the removed ones, see [`merge_metadata`].
+#[server(MergeDuplicates, "/admin/api")]
+pub async fn merge_duplicates(keep: usize, remove: Vec<usize>) -> Result<Image, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    let image = |id: usize| state.catalog.get(id)
+        .ok_or_else(|| ServerFnError::ServerError(format!("no image with id {id}")));
+    let before = image(keep)?;
+    let others = remove.iter()
+        .filter(|id| **id != keep)
+        .map(|id| image(*id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // first, so nothing is lost if removing the others fails halfway
+    let after = state.catalog
+        .update(keep, |img| merge_metadata(img, &others))
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    state.audit
+        .record(&user, keep, "merge", &before, &after)
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    for other in &others {
+        remove_image(&state, &user, other.id)?;
+    }
+
+    Ok(after)
+}
+
+#[server(DeleteImage, "/admin/api")]
+pub async fn delete_image(id: usize) -> Result<(), ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    remove_image(&state, &user, id).map(|_| ())
+}
+
+/// Takes what `image` is missing from `others`: a location, an earlier timestamp and a caption.
+/// Tags and cats are combined, so the cats' sightings are kept.
+#[cfg(feature = "ssr")]
+fn merge_metadata(image: &mut Image, others: &[Image]) {
+    for other in others {
+        if image.needs_location && !other.needs_location {
+            image.latitude = other.latitude;
+            image.longitude = other.longitude;
+            image.city = other.city.clone();
+            image.country = other.country.clone();
+            image.needs_location = false;
+        }
+
+        if other.timestamp < image.timestamp {
+            image.timestamp = other.timestamp.clone();
+        }
+
+        if image.caption.is_none() {
+            image.caption = other.caption.clone();
+        }
+        for tag in &other.tags {
+            if !image.tags.contains(tag) {
+                image.tags.push(tag.clone());
+            }
+        }
+        for cat_id in &other.cat_ids {
+            if !image.cat_ids.contains(cat_id) {
+                image.cat_ids.push(*cat_id);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn remove_image(
+    state: &crate::state::AppState,
+    user: &str,
+    id: usize,
+) -> Result<Image, ServerFnError> {
+    let image = state.catalog.remove(id)
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    if let Err(e) = crate::ingest::remove_files(&state.media_dir, &image.sha256) {
+        log::warn!("failed to delete files of photo {}: {e}", image.sha256);
+    }
+
+    state.audit
+        .record(user, id, "delete", &image, ())
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    Ok(image)
+}
+
+/// Computes the hashes of images that were added before hashes were stored at ingest.
+#[cfg(feature = "ssr")]
+async fn backfill_hashes(state: &crate::state::AppState) -> Result<(), ServerFnError> {
+    use crate::ingest::{dhash_file, format_dhash, Variant};
+
+    for image in state.catalog.images().into_iter().filter(|img| img.dhash.is_none()) {
+        let path = Variant::Small.path(&state.media_dir, &image.sha256);
+        let hash = tokio::task::spawn_blocking(move || dhash_file(&path))
+            .await
+            .expect("hashing task panicked");
+
+        match hash {
+            Ok(hash) => {
+                state.catalog
+                    .update(image.id, |img| img.dhash = Some(format_dhash(hash)))
+                    .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+            }
+            Err(e) => log::warn!("failed to hash photo #{}: {e}", image.id),
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts images into groups whose hashes are all within [`MAX_DISTANCE`] of each other, so
+/// "Keep only this" never deletes a photo that isn't close to the kept one. An image joins the
+/// first group it is close to every member of. Images without a near-duplicate are left out.
+#[cfg(feature = "ssr")]
+fn group_duplicates(images: Vec<Image>) -> Vec<DuplicateGroup> {
+    use crate::ingest::parse_dhash;
+
+    let hashed: Vec<(Image, u64)> = images.into_iter()
+        .filter_map(|img| {
+            let hash = parse_dhash(img.dhash.as_deref()?)?;
+            Some((img, hash))
+        })
+        .collect();
+
+    let distance = |i: usize, j: usize| (hashed[i].1 ^ hashed[j].1).count_ones();
+
+    // indices of `hashed`
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for i in 0..hashed.len() {
+        match groups.iter_mut().find(|group| group.iter().all(|j| distance(i, *j) <= MAX_DISTANCE)) {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+
+    groups.into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let hashes: Vec<u64> = members.iter().map(|i| hashed[*i].1).collect();
+            let max_distance = hashes.iter()
+                .flat_map(|a| hashes.iter().map(move |b| (a ^ b).count_ones()))
+                .max()
+                .unwrap_or_default();
+
+            DuplicateGroup {
+                images: members.into_iter().map(|i| hashed[i].0.clone()).collect(),
+                max_distance,
+            }
+        })
+        .collect()
+}
+
+#[component]
+pub fn AdminDuplicates() -> impl IntoView {
+    let merge = create_server_action::<MergeDuplicates>();
+    let delete = create_server_action::<DeleteImage>();
+
+    let groups = create_resource(
+        move || (merge.version().get(), delete.version().get()),
+        |_| find_duplicates(),
+    );
+
+    let action_error = move || {
+        let error = match (merge.value().get(), delete.value().get()) {
+            (Some(Err(e)), _) | (_, Some(Err(e))) => Some(e.to_string()),
+            _ => None,
+        };
+        error.map(|e| view! { <p class="upload-error">{e}</p> })
+    };
+
+    view! {
+        <script src="/map.js"></script>
+
+        <AdminNav/>
+        <h1>"Likely duplicates"</h1>
+        {action_error}
+        <Transition fallback=|| view! { <progress></progress> }>
+            {move || groups.get().map(|groups| match groups {
+                Err(e) => view! { <p class="upload-error">{e.to_string()}</p> }.into_view(),
+                Ok(groups) if groups.is_empty() => view! {
+                    <p>"No duplicates found."</p>
+                }.into_view(),
+                Ok(groups) => groups.into_iter()
+                    .map(|group| view! { <Group group merge delete/> })
+                    .collect_view(),
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+fn Group(
+    group: DuplicateGroup,
+    merge: Action<MergeDuplicates, Result<Image, ServerFnError>>,
+    delete: Action<DeleteImage, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let ids: Vec<usize> = group.images.iter().map(|img| img.id).collect();
+
+    let cards = group.images.into_iter()
+        .map(|image| {
+            let id = image.id;
+            let others = ids.clone();
+            let alt = format!("photo #{id}");
+            let location = if image.needs_location {
+                "no location".to_string()
+            } else {
+                format_location(&image)
+            };
+
+            view! {
+                <div class="fav-card">
+                    <article>
+                        <img src=image.url_small alt=alt/>
+                        <p>"#"{id}", "{image.timestamp}<br/>{location}</p>
+                    </article>
+                    <footer>
+                        <button on:click=move |_| merge.dispatch(MergeDuplicates {
+                            keep: id,
+                            remove: others.clone(),
+                        })>"Keep only this"</button>
+                        <button class="secondary" on:click=move |_| delete.dispatch(DeleteImage { id })>
+                            "Delete"
+                        </button>
+                    </footer>
+                </div>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <section class="duplicate-group">
+            <h2>{ids.len()}" photos, differing in up to "{group.max_distance}" bits"</h2>
+            {cards}
+        </section>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    fn image(id: usize, dhash: Option<u64>) -> Image {
+        Image { id, dhash: dhash.map(crate::ingest::format_dhash), ..Default::default() }
+    }
+
+    fn ids(group: &DuplicateGroup) -> Vec<usize> {
+        group.images.iter().map(|img| img.id).collect()
+    }
+
+    #[test]
+    fn groups_close_hashes() {
+        let groups = group_duplicates(vec![
+            image(1, Some(0)),
+            image(2, Some(u64::MAX)),
+            image(3, Some(0b111)),
+            image(4, Some(u64::MAX ^ 1)),
+            image(5, Some(0xffff_0000)),
+            image(6, None),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(ids(&groups[0]), [1, 3]);
+        assert_eq!(groups[0].max_distance, 3);
+        assert_eq!(ids(&groups[1]), [2, 4]);
+        assert_eq!(groups[1].max_distance, 1);
+    }
+
+    #[test]
+    fn doesnt_group_chains() {
+        // 1 and 3 are too far apart, but both are close to 2
+        let groups = group_duplicates(vec![
+            image(1, Some(0)),
+            image(2, Some(0xff)),
+            image(3, Some(0xffff)),
+        ]);
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(ids(&groups[0]), [1, 2]);
+        assert_eq!(groups[0].max_distance, 8);
+    }
+
+    #[test]
+    fn merges_metadata_of_removed_photos() {
+        let mut kept = Image {
+            timestamp: "2023-11-05T14:30:00".into(),
+            needs_location: true,
+            tags: vec!["sleeping".into()],
+            cat_ids: vec![1],
+            ..Default::default()
+        };
+        let others = [
+            Image {
+                timestamp: "2023-11-05T14:29:00".into(),
+                city: "Bangkok".into(),
+                country: "Thailand".into(),
+                tags: vec!["sleeping".into(), "orange".into()],
+                cat_ids: vec![2, 1],
+                caption: Some("On the steps".into()),
+                ..Default::default()
+            },
+            Image {
+                timestamp: "2023-11-06T09:00:00".into(),
+                caption: Some("Later caption".into()),
+                cat_ids: vec![3],
+                ..Default::default()
+            },
+        ];
+
+        merge_metadata(&mut kept, &others);
+
+        assert!(!kept.needs_location);
+        assert_eq!((kept.city.as_str(), kept.country.as_str()), ("Bangkok", "Thailand"));
+        assert_eq!(kept.timestamp, "2023-11-05T14:29:00");
+        assert_eq!(kept.caption.as_deref(), Some("On the steps"));
+        assert_eq!(kept.tags, ["sleeping", "orange"]);
+        assert_eq!(kept.cat_ids, [1, 2, 3]);
+    }
+
+    #[test]
+    fn leaves_out_unique_photos() {
+        assert!(group_duplicates(vec![image(1, Some(0)), image(2, Some(u64::MAX)), image(3, None)]).is_empty());
+    }
+}
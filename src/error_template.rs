@@ -5,6 +5,14 @@ use thiserror::Error;
 
 #[cfg(feature = "ssr")]
 use leptos_axum::ResponseOptions;
+#[cfg(feature = "ssr")]
+use crate::telemetry::request_id;
+
+/// Only the server knows, after navigating in the browser there is no request to refer to.
+#[cfg(not(feature = "ssr"))]
+fn request_id() -> Option<String> {
+    None
+}
 
 #[derive(Clone, Debug, Error)]
 pub enum AppError {
@@ -42,9 +50,9 @@ pub fn ErrorTemplate(
         .into_iter()
         .filter_map(|(_k, v)| v.downcast_ref::<AppError>().cloned())
         .collect();
-    println!("Errors: {errors:#?}");
 
     let status_code = errors[0].status_code();
+    let id = request_id();
 
     // Only the response code for the first error is actually sent from the server
     // this may be customized by the specific application
@@ -54,10 +62,27 @@ pub fn ErrorTemplate(
             response.set_status(status_code);
         }
 
+        tracing::info!(request_id = id, status = status_code.as_u16(), ?errors, "rendering error page");
     }}
 
+    // a resource, so the id rendered on the server survives hydration
+    let request_id = create_resource(|| (), move |_| {
+        let id = id.clone();
+        async move { id }
+    });
+    let request_id = view! {
+        <Suspense fallback=|| ()>
+            {move || request_id.get().flatten().map(|id| view! {
+                <p class="request-id">"Request ID: " {id}</p>
+            })}
+        </Suspense>
+    };
+
     if status_code == StatusCode::NOT_FOUND {
-        view! { <img src="404.jpg"/> }
+        view! {
+            <img src="/404.jpg"/>
+            {request_id}
+        }
         .into_view()
     } else {
         view! {
@@ -77,6 +102,7 @@ pub fn ErrorTemplate(
                     }
                 }
             />
+            {request_id}
         }
         .into_view()
     }
@@ -0,0 +1,272 @@
+use std::fmt::Write;
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use leptos_router::ParamsMap;
+use serde_json::{json, Value};
+
+use crate::api::{Cat, Image};
+use crate::cats::cat_names;
+use crate::ingest::TIMESTAMP_FORMAT;
+use crate::map::format_location;
+use crate::search::SearchQuery;
+use crate::state::AppState;
+
+/// Query parameter with a comma-separated list of sha256 hashes, to export someone's favorites.
+const FAVORITES_PARAM: &str = "favorites";
+
+/// The published images that match `params`, which are the same as for the search page plus
+/// [`FAVORITES_PARAM`].
+fn matching_images(state: &AppState, params: Vec<(String, String)>) -> (Vec<Image>, Vec<Cat>) {
+    let mut query = ParamsMap::new();
+    for (key, value) in params {
+        query.insert(key, value);
+    }
+
+    let favorites: Option<Vec<String>> = query.get(FAVORITES_PARAM)
+        .map(|hashes| hashes.split(',').map(str::to_string).collect());
+    let query = SearchQuery::from_query_map(&query);
+
+    let cats = state.catalog.cats();
+    let images = state.public_images()
+        .into_iter()
+        .filter(|img| query.matches(img, &cats))
+        .filter(|img| favorites.as_ref().is_none_or(|favorites| favorites.contains(&img.sha256)))
+        .collect();
+
+    (images, cats)
+}
+
+/// Where the site is reachable from, as configured or else as seen by the client. Google Earth and
+/// friends need absolute URLs for images.
+pub(crate) fn base_url(state: &AppState, headers: &HeaderMap) -> String {
+    match &state.public_url {
+        Some(url) => url.clone(),
+        None => request_origin(headers, state.trust_proxy),
+    }
+}
+
+/// The scheme and host the request was sent to. The forwarded ones are only believed from a
+/// trusted proxy, anyone else could point links in feeds and sitemaps to their own site.
+fn request_origin(headers: &HeaderMap, trust_proxy: bool) -> String {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let forwarded = |name: &str| trust_proxy.then(|| header(name)).flatten();
+
+    let scheme = forwarded("x-forwarded-proto").unwrap_or("http");
+    let host = forwarded("x-forwarded-host").or(header("host")).unwrap_or("localhost");
+
+    format!("{scheme}://{host}")
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Catalog timestamps don't have a time zone, so they are taken to be UTC.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|timestamp| timestamp.and_utc())
+}
+
+/// A catalog timestamp as RFC 3339 in UTC, e.g. `2023-11-05T14:30:00Z`, the format of Atom, KML
+/// and GPX. Empty if it can't be parsed.
+pub(crate) fn rfc3339(timestamp: &str) -> String {
+    parse_timestamp(timestamp)
+        .map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+fn attachment(content_type: &'static str, file_name: &str) -> [(header::HeaderName, String); 2] {
+    [
+        (header::CONTENT_TYPE, content_type.to_string()),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{file_name}\"")),
+    ]
+}
+
+/// A one line description of an image, e.g. "Photo #3 of Mr Whiskers, Bangkok, Thailand".
+pub(crate) fn title(image: &Image, cats: &[Cat]) -> String {
+    let names = cat_names(cats, &image.cat_ids);
+    match names.is_empty() {
+        true => format!("Photo #{}, {}", image.id, format_location(image)),
+        false => format!("Photo #{} of {}, {}", image.id, names.join(" and "), format_location(image)),
+    }
+}
+
+/// Serves the published images as a GeoJSON FeatureCollection, for loading into GIS tools.
+pub async fn geojson_handler(
+    State(state): State<AppState>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let (images, cats) = matching_images(&state, params);
+
+    let features: Vec<Value> = images.iter()
+        .map(|image| {
+            let mut properties = serde_json::to_value(image).expect("image to serialize");
+            if let Some(properties) = properties.as_object_mut() {
+                // they're in the geometry
+                properties.remove("latitude");
+                properties.remove("longitude");
+                properties.insert("catNames".into(), json!(cat_names(&cats, &image.cat_ids)));
+            }
+
+            json!({
+                "type": "Feature",
+                "id": image.id,
+                // GeoJSON puts longitude first
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [image.longitude, image.latitude],
+                },
+                "properties": properties,
+            })
+        })
+        .collect();
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+
+    ([(header::CONTENT_TYPE, "application/geo+json")], collection.to_string())
+}
+
+/// Serves the published images as KML placemarks showing a thumbnail, for Google Earth.
+pub async fn kml_handler(
+    State(state): State<AppState>,
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (images, cats) = matching_images(&state, params);
+    let base_url = base_url(&state, &headers);
+
+    let mut kml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document><name>Cats of Asia</name>"#, "\n",
+    ));
+
+    for image in &images {
+        // the description is HTML, which KML wants escaped or in a CDATA section
+        let description = format!(
+            r#"<a href="{base_url}/photos/{id}"><img src="{base_url}{src}" width="400"/></a><p>{caption}</p><p>{timestamp}</p>"#,
+            id = image.id,
+            src = image.url_small,
+            caption = escape_xml(image.caption.as_deref().unwrap_or_default()),
+            timestamp = image.timestamp,
+        );
+
+        let when = match rfc3339(&image.timestamp) {
+            when if when.is_empty() => String::new(),
+            when => format!("<TimeStamp><when>{when}</when></TimeStamp>"),
+        };
+
+        let _ = writeln!(
+            kml,
+            "<Placemark><name>{}</name><description>{}</description>{when}<Point><coordinates>{},{}</coordinates></Point></Placemark>",
+            escape_xml(&title(image, &cats)),
+            escape_xml(&description),
+            image.longitude,
+            image.latitude,
+        );
+    }
+
+    kml.push_str("</Document></kml>\n");
+    (attachment("application/vnd.google-earth.kml+xml", "cats-of-asia.kml"), kml)
+}
+
+/// Serves the published images as GPX waypoints plus a track through them in the order they were
+/// taken, for hiking and travel apps.
+pub async fn gpx_handler(
+    State(state): State<AppState>,
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (mut images, cats) = matching_images(&state, params);
+    images.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    let base_url = base_url(&state, &headers);
+
+    let mut gpx = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<gpx version="1.1" creator="Cats of Asia" xmlns="http://www.topografix.com/GPX/1/1">"#, "\n",
+    ));
+
+    // GPX wants UTC, and nothing rather than an empty time
+    let time = |image: &Image| match rfc3339(&image.timestamp) {
+        time if time.is_empty() => String::new(),
+        time => format!("<time>{time}</time>"),
+    };
+
+    for image in &images {
+        let _ = writeln!(
+            gpx,
+            r#"<wpt lat="{}" lon="{}">{}<name>{}</name><desc>{}</desc><link href="{}/photos/{}"><text>Photo #{}</text></link></wpt>"#,
+            image.latitude,
+            image.longitude,
+            time(image),
+            escape_xml(&title(image, &cats)),
+            escape_xml(image.caption.as_deref().unwrap_or_default()),
+            escape_xml(&base_url),
+            image.id,
+            image.id,
+        );
+    }
+
+    gpx.push_str("<trk><name>Cats of Asia</name><trkseg>\n");
+    for image in &images {
+        let _ = writeln!(
+            gpx,
+            r#"<trkpt lat="{}" lon="{}">{}</trkpt>"#,
+            image.latitude,
+            image.longitude,
+            time(image),
+        );
+    }
+    gpx.push_str("</trkseg></trk></gpx>\n");
+
+    (attachment("application/gpx+xml", "cats-of-asia.gpx"), gpx)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn header_map(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (header::HeaderName::from_static(name), HeaderValue::from_static(value))).collect()
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(rfc3339("2023-11-05T14:30:00"), "2023-11-05T14:30:00Z");
+        assert_eq!(rfc3339("2023-11-05"), "");
+    }
+
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(escape_xml(r#"Tom & "Jerry" <'s>"#), "Tom &amp; &quot;Jerry&quot; &lt;&apos;s&gt;");
+        // escaping twice shows the first escape
+        assert_eq!(escape_xml("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_without_a_proxy() {
+        let headers = header_map(&[("host", "catsof.asia"), ("x-forwarded-host", "evil.example"), ("x-forwarded-proto", "https")]);
+        assert_eq!(request_origin(&headers, false), "http://catsof.asia");
+    }
+
+    #[test]
+    fn uses_forwarded_headers_from_a_trusted_proxy() {
+        let headers = header_map(&[("host", "localhost:3000"), ("x-forwarded-host", "catsof.asia"), ("x-forwarded-proto", "https")]);
+        assert_eq!(request_origin(&headers, true), "https://catsof.asia");
+
+        let headers = header_map(&[("host", "catsof.asia")]);
+        assert_eq!(request_origin(&headers, true), "http://catsof.asia");
+    }
+}
@@ -1,41 +1,86 @@
+use std::iter;
+
 use leptos::*;
-use web_sys::MouseEvent;
-use gloo_storage::{Storage, LocalStorage};
+use web_sys::DragEvent;
 
 use crate::api::ImagesResource;
+use crate::collections::ShareCollection;
+use crate::favorites_file::ImportExport;
+use crate::favorites_store::{Favorite, FavoritesStore};
+use crate::lightbox::{use_lightbox_href, Lightbox};
+use crate::search::DownloadLinks;
+
+/// What the parts of the favorites page share. The store is read from LocalStorage again whenever
+/// it changes.
+#[derive(Clone, Copy)]
+struct FavoritesState {
+    store: Resource<usize, FavoritesStore>,
+    reload: RwSignal<usize>,
+}
+
+impl FavoritesState {
+    fn folders(&self) -> Vec<String> {
+        self.store.get().map(|store| store.folders).unwrap_or_default()
+    }
+
+    fn modify(&self, change: impl FnOnce(&mut FavoritesStore)) {
+        FavoritesStore::modify(change);
+        self.reload.update(|r| *r += 1);
+    }
+
+    /// Moves the favorite that is being dragged, see [`FavoriteCard`].
+    fn drop(&self, ev: &DragEvent, folder: Option<String>, before: Option<&str>) {
+        let Some(hash) = ev.data_transfer().and_then(|dt| dt.get_data("text/plain").ok()) else {
+            return;
+        };
+        self.modify(|store| store.move_item(&hash, folder, before));
+    }
+}
 
 #[component]
 pub fn Favorites() -> impl IntoView {
     let reload = create_rw_signal(0);
-    
-    let favorites = create_local_resource(
+    let store = create_local_resource(
         reload,
-        |_| async move {load_favorites()}
+        |_| async move { FavoritesStore::load() }
     );
+    let favorites = FavoritesState { store, reload };
+
+    let hashes = move || store().map(|store| store.hashes()).unwrap_or_default();
+
+    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let favorite_images = Signal::derive(move || {
+        let all = images.0.get().unwrap_or_default();
+        hashes()
+            .into_iter()
+            .filter_map(|hash| all.iter().find(|img| img.sha256 == hash).cloned())
+            .collect::<Vec<_>>()
+    });
+
+    // favorites outside of any folder come first
+    let sections = move || iter::once(None)
+        .chain(favorites.folders().into_iter().map(Some))
+        .collect::<Vec<_>>();
 
     view! {
         <>
-            <script src="map.js"></script>
-    
+            <script src="/map.js"></script>
+            <Lightbox images=favorite_images/>
+            <ImportExport on_import=move |_| reload.update(|r| *r += 1)/>
+            <Show when=move || !hashes().is_empty() fallback=|| ()>
+                <DownloadLinks query=move || format!("favorites={}", hashes().join(","))/>
+                <ShareCollection/>
+                <NewFolder favorites/>
+            </Show>
+
             <Show
-                when=move || { favorites().is_some() && has_favorites() }
+                when=move || !hashes().is_empty()
                 fallback=NoFavorites
             >
                 <For
-                    each=move || favorites().unwrap().into_iter()
-                    key=move |hash| hash.clone()
-    
-                    children=move |hash| {
-                        view! {
-                            <Favorite
-                                hash=hash.clone()
-                                on_delete=move |_| {
-                                    remove_favorite(&hash);
-                                    reload.set(reload.get()+1);
-                                }
-                            />
-                        }
-                    }
+                    each=sections
+                    key=|folder| folder.clone()
+                    children=move |folder| view! { <Folder folder favorites/> }
                 />
             </Show>
         </>
@@ -43,66 +88,172 @@ pub fn Favorites() -> impl IntoView {
 }
 
 #[component]
-fn Favorite(
-    hash: String,
-    #[prop(into)]
-    on_delete: Callback<MouseEvent>
-) -> impl IntoView {
-    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+fn NewFolder(favorites: FavoritesState) -> impl IntoView {
+    let (name, set_name) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let name = name.get_untracked().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
 
-    // this all seems very wrong
-    let image = (|| {
-        if let Some(images) = images.0.get() {
-            for img in images {
-                if img.sha256 == hash {
-                    return Some(img.clone());
-                }
-            }
+        let mut added = false;
+        favorites.modify(|store| added = store.add_folder(name.clone()));
+        if added {
+            set_name(String::new());
+            set_error(None);
+        } else {
+            set_error(Some(format!("There already is a folder called \"{name}\".")));
         }
-        None
-    })();
-
-    // <Show> hates me
-    if let Some(image) = image {
-        let alt = format!("photo #{} showing one or more cats", image.id);
-        let url_medium = image.url_medium.clone();
-        let url_large = image.url_large.clone();
-
-        view! {
-            <div class="fav-card">
-                <article>
-                    <a href={url_large}><img src={url_medium} alt={alt} /></a>
-                </article>
-                <footer>
-                    <button on:click=on_delete>"Remove"</button>
-                </footer>
+    };
+
+    view! {
+        <form on:submit=on_submit>
+            <div class="grid">
+                <input
+                    placeholder="New folder, e.g. Bangkok trip"
+                    prop:value=name
+                    on:input=move |ev| set_name(event_target_value(&ev))
+                />
+                <input type="submit" class="secondary" value="Add folder"/>
             </div>
-        }.into_view()
-    } else {
-        view! {
-            <></>
-        }.into_view()
+        </form>
+        {move || error().map(|e| view! { <p class="upload-error">{e}</p> })}
     }
 }
 
+/// The favorites in `folder`, or those outside of any folder for `None`. Favorites dropped on it
+/// go to the end.
 #[component]
-pub fn NoFavorites() -> impl IntoView {
+fn Folder(folder: Option<String>, favorites: FavoritesState) -> impl IntoView {
+    let items = {
+        let folder = folder.clone();
+        move || favorites.store.get()
+            .map(|store| store.in_folder(folder.as_deref()))
+            .unwrap_or_default()
+    };
+
+    let on_drop = {
+        let folder = folder.clone();
+        move |ev: DragEvent| {
+            ev.prevent_default();
+            favorites.drop(&ev, folder.clone(), None);
+        }
+    };
+
+    let heading = match folder {
+        Some(name) => {
+            let on_delete = {
+                let name = name.clone();
+                move |_| favorites.modify(|store| store.remove_folder(&name))
+            };
+            view! {
+                <header>
+                    <h2>{name}</h2>
+                    <button class="secondary outline" on:click=on_delete>"Delete folder"</button>
+                </header>
+            }.into_view()
+        }
+        // only worth a heading when there are folders to tell it apart from
+        None => view! {
+            <Show when=move || !favorites.folders().is_empty() fallback=|| ()>
+                <header><h2>"Not in a folder"</h2></header>
+            </Show>
+        }.into_view(),
+    };
+
     view! {
-        <div style="display: flex; justify-content: center;">
-            "You don't have any favorites yet. Find some on the"<a href="/">"map"</a>"!"
-        </div>
+        <section
+            class="fav-folder"
+            on:dragover=|ev: DragEvent| ev.prevent_default()
+            on:drop=on_drop
+        >
+            {heading}
+            <For
+                each=items
+                key=|item| item.sha256.clone()
+                children=move |item| view! { <FavoriteCard item favorites/> }
+            />
+        </section>
     }
 }
 
-fn has_favorites() -> bool {
-    !load_favorites().is_empty()
+/// A favorite that can be dragged in front of another one, or into another folder.
+#[component]
+fn FavoriteCard(item: Favorite, favorites: FavoritesState) -> impl IntoView {
+    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let Favorite { sha256, note, folder } = item;
+    let hash = store_value(sha256);
+
+    let image = move || images.0.get()
+        .and_then(|images| images.into_iter().find(|img| hash.with_value(|hash| img.sha256 == *hash)))
+        .map(|image| {
+            let alt = format!("photo #{} showing one or more cats", image.id);
+            let href = use_lightbox_href()(image.id);
+            view! { <a href={href}><img src={image.url_medium} alt={alt} draggable="false"/></a> }
+        });
+
+    let on_drag_start = move |ev: DragEvent| {
+        if let Some(dt) = ev.data_transfer() {
+            dt.set_data("text/plain", &hash.get_value()).ok();
+        }
+    };
+    let on_drop = {
+        let folder = folder.clone();
+        move |ev: DragEvent| {
+            ev.prevent_default();
+            // the folder would move it to the end otherwise
+            ev.stop_propagation();
+            favorites.drop(&ev, folder.clone(), Some(&hash.get_value()));
+        }
+    };
+
+    let on_note = move |ev| {
+        let note = event_target_value(&ev);
+        favorites.modify(|store| hash.with_value(|hash| store.set_note(hash, note)));
+    };
+    let on_move = move |ev| {
+        let folder = Some(event_target_value(&ev)).filter(|folder| !folder.is_empty());
+        favorites.modify(|store| hash.with_value(|hash| store.move_item(hash, folder, None)));
+    };
+    let on_delete = move |_| favorites.modify(|store| hash.with_value(|hash| store.remove(hash)));
+
+    let folder_options = move || favorites.folders().into_iter()
+        .map(|name| {
+            let selected = folder.as_deref() == Some(name.as_str());
+            view! { <option value=name.clone() selected=selected>{name}</option> }
+        })
+        .collect_view();
+
+    view! {
+        <div class="fav-card" draggable="true" on:dragstart=on_drag_start on:drop=on_drop>
+            <article>
+                {image}
+                <textarea placeholder="Add a note" on:change=on_note>{note}</textarea>
+            </article>
+            <footer>
+                <select aria-label="Folder" on:change=on_move>
+                    <option value="">"No folder"</option>
+                    {folder_options}
+                </select>
+                <button on:click=on_delete>"Remove"</button>
+            </footer>
+        </div>
+    }
 }
 
-fn load_favorites() -> Vec<String> {
-    LocalStorage::get("favorites").unwrap_or_default()
+#[component]
+pub fn NoFavorites() -> impl IntoView {
+    view! {
+        <div class="no-favorites">
+            "You don't have any favorites yet. Find some on the"<a href="/">"map"</a>"!"
+        </div>
+    }
 }
 
-fn remove_favorite(hash: &str) {
-    let favs: Vec<String> = load_favorites().into_iter().filter(|h| h != hash).collect();
-    LocalStorage::set("favorites", favs).ok();
+/// The hashes of all favorites, in the order they are shown in.
+pub(crate) fn load_favorites() -> Vec<String> {
+    FavoritesStore::load().hashes()
 }
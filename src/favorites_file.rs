@@ -0,0 +1,310 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{HtmlAnchorElement, HtmlInputElement};
+
+use crate::api::{Image, ImagesResource};
+use crate::favorites_store::{Favorite, FavoritesStore};
+
+const FILE_VERSION: u32 = 1;
+
+/// A backup of someone's favorites. Only the hashes matter for importing, the rest is there so
+/// people can tell what they're looking at.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FavoritesFile {
+    pub version: u32,
+    #[serde(rename="exportedAt")]
+    pub exported_at: String,
+    pub favorites: Vec<FavoriteEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FavoriteEntry {
+    pub sha256: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub id: Option<usize>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default, skip_serializing_if="String::is_empty")]
+    pub note: String,
+}
+
+impl FavoriteEntry {
+    fn new(favorite: Favorite, images: &[Image]) -> FavoriteEntry {
+        let Favorite { sha256, note, folder } = favorite;
+        let image = images.iter().find(|img| img.sha256 == sha256);
+        FavoriteEntry {
+            sha256,
+            id: image.map(|img| img.id),
+            timestamp: image.map(|img| img.timestamp.clone()),
+            city: image.map(|img| img.city.clone()),
+            country: image.map(|img| img.country.clone()),
+            folder,
+            note,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let place = [self.city.as_deref(), self.country.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        match (self.id, place.is_empty()) {
+            (Some(id), false) => format!("#{id} from {place}"),
+            (Some(id), true) => format!("#{id}"),
+            (None, _) => self.sha256.chars().take(12).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: usize,
+    pub already_present: usize,
+    /// Entries whose photo is no longer in the catalog. They are not imported.
+    pub missing: Vec<FavoriteEntry>,
+}
+
+/// Merges `file` into `favorites`, skipping photos that don't exist anymore. Favorites that are
+/// already there keep their folder and note.
+pub fn merge(favorites: &mut FavoritesStore, file: FavoritesFile, images: &[Image]) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for entry in file.favorites {
+        if favorites.contains(&entry.sha256) {
+            report.already_present += 1;
+        } else if images.iter().any(|img| img.sha256 == entry.sha256) {
+            favorites.add(entry.sha256.clone());
+            favorites.set_note(&entry.sha256, entry.note);
+            if let Some(folder) = entry.folder {
+                favorites.add_folder(folder.clone());
+                favorites.move_item(&entry.sha256, Some(folder), None);
+            }
+            report.added += 1;
+        } else {
+            report.missing.push(entry);
+        }
+    }
+
+    report
+}
+
+fn today() -> String {
+    String::from(js_sys::Date::new_0().to_iso_string())
+}
+
+/// Makes the browser download `contents` as a file.
+fn download(file_name: &str, contents: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&contents.into());
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("application/json");
+
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let link: HtmlAnchorElement = document().create_element("a")?.unchecked_into();
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+async fn read_file(input: &HtmlInputElement) -> Result<String, String> {
+    let file = input.files()
+        .and_then(|files| files.get(0))
+        .ok_or("no file selected")?;
+
+    let text = wasm_bindgen_futures::JsFuture::from(file.text()).await
+        .map_err(|_| "couldn't read the file")?;
+
+    text.as_string().ok_or_else(|| "couldn't read the file".to_string())
+}
+
+fn parse(text: &str) -> Result<FavoritesFile, String> {
+    let value = js_sys::JSON::parse(text).map_err(|_| "this isn't a JSON file")?;
+    let file: FavoritesFile = serde_wasm_bindgen::from_value(value)
+        .map_err(|e| format!("this isn't a favorites file: {e}"))?;
+
+    if file.version > FILE_VERSION {
+        return Err("this file was made by a newer version of the site".into());
+    }
+    Ok(file)
+}
+
+#[component]
+pub fn ImportExport(
+    #[prop(into)]
+    on_import: Callback<()>,
+    ) -> impl IntoView {
+    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let (report, set_report) = create_signal(None::<Result<ImportReport, String>>);
+    let input = create_node_ref::<html::Input>();
+
+    let on_export = move |_| {
+        let images = images.0.get().unwrap_or_default();
+        let file = FavoritesFile {
+            version: FILE_VERSION,
+            exported_at: today(),
+            favorites: FavoritesStore::load().items.into_iter()
+                .map(|favorite| FavoriteEntry::new(favorite, &images))
+                .collect(),
+        };
+
+        let result = serde_wasm_bindgen::to_value(&file)
+            .map_err(JsValue::from)
+            .and_then(|value| js_sys::JSON::stringify_with_replacer_and_space(&value, &JsValue::NULL, &2.into()))
+            .and_then(|json| download("cats-of-asia-favorites.json", &String::from(json)));
+
+        if let Err(e) = result {
+            log::error!("failed to export favorites: {e:?}");
+        }
+    };
+
+    let on_file = move |_| {
+        let Some(input) = input.get_untracked() else {
+            return;
+        };
+
+        spawn_local(async move {
+            let result = read_file(&input).await
+                .and_then(|text| parse(&text))
+                .and_then(|file| {
+                    // without them every entry would look like a deleted photo
+                    let images = images.0.get()
+                        .ok_or_else(|| "The photos are still loading, try again in a moment.".to_string())?;
+                    let mut favorites = FavoritesStore::try_load()
+                        .map_err(|e| format!("The favorites already in this browser can't be read: {e}"))?;
+                    let report = merge(&mut favorites, file, &images);
+                    favorites.save();
+                    Ok(report)
+                });
+
+            // allow importing the same file again
+            input.set_value("");
+            set_report(Some(result));
+            on_import(());
+        });
+    };
+
+    view! {
+        <div class="grid">
+            <button class="secondary" on:click=on_export>"Export"</button>
+            <label role="button" class="secondary" aria-disabled=move || images.0.get().is_none().to_string()>
+                "Import"
+                <input
+                    type="file"
+                    accept="application/json,.json"
+                    hidden
+                    disabled=move || images.0.get().is_none()
+                    node_ref=input
+                    on:change=on_file
+                />
+            </label>
+        </div>
+        {move || report().map(|report| match report {
+            Err(e) => view! { <p class="upload-error">{e}</p> }.into_view(),
+            Ok(report) => view! { <ImportSummary report/> }.into_view(),
+        })}
+    }
+}
+
+#[component]
+fn ImportSummary(report: ImportReport) -> impl IntoView {
+    let missing = report.missing.iter()
+        .map(|entry| view! { <li>{entry.describe()}</li> })
+        .collect_view();
+
+    view! {
+        <article>
+            <p>
+                "Imported "{report.added}" favorites, "
+                {report.already_present}" were already there."
+            </p>
+            {(!report.missing.is_empty()).then(|| view! {
+                <p>{report.missing.len()}" photos are no longer on the site and were skipped:"</p>
+                <ul>{missing}</ul>
+            })}
+        </article>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(sha256: &str, folder: Option<&str>, note: &str) -> FavoriteEntry {
+        FavoriteEntry {
+            sha256: sha256.into(),
+            id: None,
+            timestamp: None,
+            city: None,
+            country: None,
+            folder: folder.map(str::to_string),
+            note: note.into(),
+        }
+    }
+
+    fn image(sha256: &str) -> Image {
+        Image { sha256: sha256.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn imports_favorites_with_folder_and_note() {
+        let mut favorites = FavoritesStore::default();
+        let file = FavoritesFile {
+            version: FILE_VERSION,
+            exported_at: String::new(),
+            favorites: vec![entry("a", Some("trip"), "sleepy"), entry("b", None, "")],
+        };
+
+        let report = merge(&mut favorites, file, &[image("a"), image("b")]);
+
+        assert_eq!(report, ImportReport { added: 2, already_present: 0, missing: vec![] });
+        assert_eq!(favorites.folders, ["trip"]);
+        assert_eq!(favorites.in_folder(Some("trip"))[0].note, "sleepy");
+        assert_eq!(favorites.in_folder(None)[0].sha256, "b");
+    }
+
+    #[test]
+    fn keeps_favorites_that_are_already_there() {
+        let mut favorites = FavoritesStore::default();
+        favorites.add("a".into());
+        favorites.set_note("a", "mine".into());
+        let file = FavoritesFile {
+            version: FILE_VERSION,
+            exported_at: String::new(),
+            favorites: vec![entry("a", Some("trip"), "theirs")],
+        };
+
+        let report = merge(&mut favorites, file, &[image("a")]);
+
+        assert_eq!(report.already_present, 1);
+        assert!(favorites.folders.is_empty());
+        assert_eq!(favorites.in_folder(None)[0].note, "mine");
+    }
+
+    #[test]
+    fn skips_photos_that_are_gone() {
+        let mut favorites = FavoritesStore::default();
+        let file = FavoritesFile {
+            version: FILE_VERSION,
+            exported_at: String::new(),
+            favorites: vec![entry("gone", None, "")],
+        };
+
+        let report = merge(&mut favorites, file, &[image("a")]);
+
+        assert_eq!(report.missing, [entry("gone", None, "")]);
+        assert!(favorites.items.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use gloo_storage::errors::StorageError;
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+/// The LocalStorage key. `map.js` reads and writes it too, so keep the two in sync.
+const STORAGE_KEY: &str = "favorites";
+const STORE_VERSION: u32 = 2;
+
+/// Someone's favorites as kept in their browser.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FavoritesStore {
+    pub version: u32,
+    /// Folder names, in the order they are shown in.
+    pub folders: Vec<String>,
+    /// In the order they are shown in, across all folders.
+    pub items: Vec<Favorite>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Favorite {
+    pub sha256: String,
+    #[serde(default)]
+    pub note: String,
+    /// `None` for favorites that aren't in a folder.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub folder: Option<String>,
+}
+
+/// Everything that was ever stored under [`STORAGE_KEY`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    /// Version 1, the hashes in the order they were added.
+    Flat(Vec<String>),
+    Versioned(FavoritesStore),
+}
+
+impl Default for FavoritesStore {
+    fn default() -> Self {
+        FavoritesStore { version: STORE_VERSION, folders: vec![], items: vec![] }
+    }
+}
+
+impl From<Stored> for FavoritesStore {
+    fn from(stored: Stored) -> Self {
+        match stored {
+            Stored::Flat(hashes) => {
+                let mut store = FavoritesStore::default();
+                for hash in hashes {
+                    store.add(hash);
+                }
+                store
+            }
+            Stored::Versioned(mut store) => {
+                store.version = STORE_VERSION;
+                store
+            }
+        }
+    }
+}
+
+impl FavoritesStore {
+    /// Loads the favorites, upgrading older formats on the way. Fails if something is stored that
+    /// can't be read, which must not be overwritten.
+    pub fn try_load() -> Result<FavoritesStore, StorageError> {
+        match LocalStorage::get::<Stored>(STORAGE_KEY) {
+            Ok(stored) => Ok(stored.into()),
+            Err(StorageError::KeyNotFound(_)) => Ok(FavoritesStore::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Loads the favorites to show them, there are none if they can't be read.
+    pub fn load() -> FavoritesStore {
+        FavoritesStore::try_load().unwrap_or_else(|e| {
+            log::error!("failed to load favorites: {e}");
+            FavoritesStore::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = LocalStorage::set(STORAGE_KEY, self) {
+            log::error!("failed to save favorites: {e}");
+        }
+    }
+
+    /// Loads the favorites, applies `change` and saves them again. Favorites that can't be loaded
+    /// are left alone rather than replaced with an empty list.
+    pub fn modify(change: impl FnOnce(&mut FavoritesStore)) {
+        match FavoritesStore::try_load() {
+            Ok(mut store) => {
+                change(&mut store);
+                store.save();
+            }
+            Err(e) => log::error!("not saving favorites, the stored ones can't be loaded: {e}"),
+        }
+    }
+
+    pub fn hashes(&self) -> Vec<String> {
+        self.items.iter().map(|item| item.sha256.clone()).collect()
+    }
+
+    pub fn contains(&self, sha256: &str) -> bool {
+        self.items.iter().any(|item| item.sha256 == sha256)
+    }
+
+    pub fn in_folder(&self, folder: Option<&str>) -> Vec<Favorite> {
+        self.items.iter()
+            .filter(|item| item.folder.as_deref() == folder)
+            .cloned()
+            .collect()
+    }
+
+    /// Adds a favorite outside of any folder, unless it's already there.
+    pub fn add(&mut self, sha256: String) {
+        if !self.contains(&sha256) {
+            self.items.push(Favorite { sha256, note: String::new(), folder: None });
+        }
+    }
+
+    pub fn remove(&mut self, sha256: &str) {
+        self.items.retain(|item| item.sha256 != sha256);
+    }
+
+    pub fn set_note(&mut self, sha256: &str, note: String) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.sha256 == sha256) {
+            item.note = note;
+        }
+    }
+
+    /// Adds a folder, returns false if there already is one with that name.
+    pub fn add_folder(&mut self, name: String) -> bool {
+        if self.folders.contains(&name) {
+            return false;
+        }
+        self.folders.push(name);
+        true
+    }
+
+    /// Deletes a folder, the favorites in it are kept outside of any folder.
+    pub fn remove_folder(&mut self, name: &str) {
+        self.folders.retain(|folder| folder != name);
+        for item in &mut self.items {
+            if item.folder.as_deref() == Some(name) {
+                item.folder = None;
+            }
+        }
+    }
+
+    /// Moves a favorite into `folder`, in front of `before` or to the end of the folder.
+    pub fn move_item(&mut self, sha256: &str, folder: Option<String>, before: Option<&str>) {
+        if before == Some(sha256) {
+            return;
+        }
+        let Some(index) = self.items.iter().position(|item| item.sha256 == sha256) else {
+            return;
+        };
+
+        let mut item = self.items.remove(index);
+        item.folder = folder.filter(|folder| self.folders.contains(folder));
+
+        let position = match before {
+            Some(before) => self.items.iter().position(|item| item.sha256 == before),
+            None => None,
+        };
+        match position {
+            Some(position) => self.items.insert(position, item),
+            None => self.items.push(item),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> FavoritesStore {
+        serde_json::from_str::<Stored>(json).unwrap().into()
+    }
+
+    fn store(items: &[(&str, Option<&str>)]) -> FavoritesStore {
+        FavoritesStore {
+            version: STORE_VERSION,
+            folders: vec!["trip".into(), "kittens".into()],
+            items: items.iter()
+                .map(|(sha256, folder)| Favorite {
+                    sha256: sha256.to_string(),
+                    note: String::new(),
+                    folder: folder.map(str::to_string),
+                })
+                .collect(),
+        }
+    }
+
+    fn order(store: &FavoritesStore) -> Vec<(&str, Option<&str>)> {
+        store.items.iter().map(|item| (item.sha256.as_str(), item.folder.as_deref())).collect()
+    }
+
+    #[test]
+    fn migrates_flat_list() {
+        let store = parse(r#"["a", "b", "a", "c"]"#);
+
+        assert_eq!(store.version, STORE_VERSION);
+        assert!(store.folders.is_empty());
+        assert_eq!(order(&store), [("a", None), ("b", None), ("c", None)]);
+    }
+
+    #[test]
+    fn reads_current_version() {
+        let stored = store(&[("a", Some("trip")), ("b", None)]);
+        let json = serde_json::to_string(&stored).unwrap();
+
+        assert_eq!(parse(&json), stored);
+        // the note is optional
+        assert_eq!(parse(r#"{"version": 2, "folders": [], "items": [{"sha256": "a"}]}"#).items[0].note, "");
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(serde_json::from_str::<Stored>(r#"{"favorites": ["a"]}"#).is_err());
+    }
+
+    #[test]
+    fn moves_in_front_of_another() {
+        let mut store = store(&[("a", None), ("b", None), ("c", None)]);
+
+        store.move_item("c", None, Some("a"));
+        assert_eq!(order(&store), [("c", None), ("a", None), ("b", None)]);
+
+        store.move_item("c", None, Some("c"));
+        assert_eq!(order(&store), [("c", None), ("a", None), ("b", None)]);
+    }
+
+    #[test]
+    fn moves_into_folders() {
+        let mut store = store(&[("a", None), ("b", Some("trip")), ("c", None)]);
+
+        store.move_item("a", Some("trip".into()), None);
+        assert_eq!(order(&store), [("b", Some("trip")), ("c", None), ("a", Some("trip"))]);
+
+        store.move_item("c", Some("trip".into()), Some("b"));
+        assert_eq!(order(&store), [("c", Some("trip")), ("b", Some("trip")), ("a", Some("trip"))]);
+
+        // there is no such folder
+        store.move_item("b", Some("nope".into()), None);
+        assert_eq!(order(&store), [("c", Some("trip")), ("a", Some("trip")), ("b", None)]);
+
+        store.move_item("unknown", None, None);
+        assert_eq!(store.items.len(), 3);
+    }
+
+    #[test]
+    fn keeps_favorites_of_removed_folders() {
+        let mut store = store(&[("a", Some("trip")), ("b", Some("kittens"))]);
+
+        store.remove_folder("trip");
+        assert_eq!(store.folders, ["kittens"]);
+        assert_eq!(order(&store), [("a", None), ("b", Some("kittens"))]);
+    }
+}
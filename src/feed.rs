@@ -0,0 +1,196 @@
+use std::fmt::Write;
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::api::{Cat, Image};
+use crate::export::{base_url, escape_xml, parse_timestamp, rfc3339, title};
+use crate::map::format_location;
+use crate::places::{country_name, place_slug};
+use crate::state::AppState;
+
+const FEED_LENGTH: usize = 50;
+
+/// The date of undated Atom feeds and entries, fixed so readers don't take it for news.
+const NO_DATE: &str = "1970-01-01T00:00:00Z";
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    /// Name or slug, for the photos from a single country.
+    country: Option<String>,
+}
+
+/// The newest photos for a feed, i.e. those with the highest ids as they are numbered in the
+/// order they were added.
+struct Feed {
+    title: String,
+    /// Where the feed is served from, relative to the site.
+    path: String,
+    images: Vec<Image>,
+    cats: Vec<Cat>,
+    base_url: String,
+}
+
+impl Feed {
+    /// `None` if there never were photos from the requested country.
+    fn new(state: &AppState, query: FeedQuery, headers: &HeaderMap, extension: &str) -> Option<Feed> {
+        let mut images = state.public_images();
+        let mut title = "Cats of Asia".to_string();
+        let mut path = format!("/feed.{extension}");
+
+        if let Some(country) = query.country {
+            let slug = place_slug(&country);
+            images.retain(|img| place_slug(&img.country) == slug);
+            let name = country_name(&images.first()?.country).to_string();
+
+            title = format!("Cats of {name}");
+            path = format!("{path}?country={slug}");
+        }
+
+        images.sort_by_key(|img| std::cmp::Reverse(img.id));
+        images.truncate(FEED_LENGTH);
+
+        Some(Feed { title, path, images, cats: state.catalog.cats(), base_url: base_url(state, headers) })
+    }
+
+    fn photo_url(&self, image: &Image) -> String {
+        format!("{}/photos/{}", self.base_url, image.id)
+    }
+
+    /// What feed readers show for a photo, as HTML.
+    fn content(&self, image: &Image) -> String {
+        let caption = image.caption.as_deref()
+            .map(|caption| format!("<p>{}</p>", escape_xml(caption)))
+            .unwrap_or_default();
+
+        format!(
+            r#"<a href="{url}"><img src="{base_url}{src}" alt="photo #{id} showing one or more cats"/></a>{caption}<p>{location}, {timestamp}</p>"#,
+            url = escape_xml(&self.photo_url(image)),
+            base_url = escape_xml(&self.base_url),
+            src = escape_xml(&image.url_medium),
+            id = image.id,
+            location = escape_xml(&format_location(image)),
+            timestamp = escape_xml(&image.timestamp.replace('T', " ")),
+        )
+    }
+}
+
+fn rfc2822(timestamp: &str) -> String {
+    parse_timestamp(timestamp)
+        .map(|timestamp| timestamp.to_rfc2822())
+        .unwrap_or_default()
+}
+
+/// Atom requires dates. Feeds without photos and photos whose timestamp can't be parsed get
+/// [`NO_DATE`].
+fn atom_date(timestamp: &str) -> String {
+    match rfc3339(timestamp) {
+        date if date.is_empty() => NO_DATE.to_string(),
+        date => date,
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "no photos from this country").into_response()
+}
+
+/// Serves the newest photos as an Atom feed, optionally of a single country.
+pub async fn atom_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(feed) = Feed::new(&state, query, &headers, "atom") else {
+        return not_found();
+    };
+
+    let updated = feed.images.iter().map(|img| img.timestamp.as_str()).max().unwrap_or_default();
+    let mut atom = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<feed xmlns="http://www.w3.org/2005/Atom">"#, "\n",
+    ));
+    let _ = writeln!(
+        atom,
+        r#"<id>{base_url}{path}</id><title>{title}</title><updated>{updated}</updated><link rel="self" href="{base_url}{path}"/><link href="{base_url}/"/>"#,
+        base_url = escape_xml(&feed.base_url),
+        path = escape_xml(&feed.path),
+        title = escape_xml(&feed.title),
+        updated = atom_date(updated),
+    );
+
+    for image in &feed.images {
+        let url = escape_xml(&feed.photo_url(image));
+        let _ = writeln!(
+            atom,
+            r#"<entry><id>{url}</id><title>{title}</title><updated>{updated}</updated><author><name>Cats of Asia</name></author><link href="{url}"/><content type="html">{content}</content></entry>"#,
+            title = escape_xml(&title(image, &feed.cats)),
+            updated = atom_date(&image.timestamp),
+            content = escape_xml(&feed.content(image)),
+        );
+    }
+    atom.push_str("</feed>\n");
+
+    ([(header::CONTENT_TYPE, "application/atom+xml")], atom).into_response()
+}
+
+/// Serves the newest photos as an RSS 2.0 feed, for readers that don't do Atom.
+pub async fn rss_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(feed) = Feed::new(&state, query, &headers, "rss") else {
+        return not_found();
+    };
+
+    let mut rss = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel>"#, "\n",
+    ));
+    let _ = writeln!(
+        rss,
+        r#"<title>{title}</title><link>{base_url}/</link><description>The newest photos of cats on Cats of Asia</description><atom:link rel="self" type="application/rss+xml" href="{base_url}{path}"/>"#,
+        base_url = escape_xml(&feed.base_url),
+        path = escape_xml(&feed.path),
+        title = escape_xml(&feed.title),
+    );
+
+    for image in &feed.images {
+        let url = escape_xml(&feed.photo_url(image));
+        let _ = writeln!(
+            rss,
+            r#"<item><title>{title}</title><link>{url}</link><guid isPermaLink="true">{url}</guid><pubDate>{date}</pubDate><description>{content}</description></item>"#,
+            title = escape_xml(&title(image, &feed.cats)),
+            date = rfc2822(&image.timestamp),
+            content = escape_xml(&feed.content(image)),
+        );
+    }
+    rss.push_str("</channel></rss>\n");
+
+    ([(header::CONTENT_TYPE, "application/rss+xml")], rss).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_catalog_timestamps_as_utc() {
+        assert_eq!(rfc3339("2023-11-05T14:30:00"), "2023-11-05T14:30:00Z");
+        assert_eq!(rfc2822("2023-11-05T14:30:00"), "Sun, 5 Nov 2023 14:30:00 +0000");
+    }
+
+    #[test]
+    fn leaves_unparseable_timestamps_empty() {
+        assert_eq!(rfc3339(""), "");
+        assert_eq!(rfc2822("yesterday"), "");
+    }
+
+    #[test]
+    fn always_dates_atom_feeds() {
+        assert_eq!(atom_date("2023-11-05T14:30:00"), "2023-11-05T14:30:00Z");
+        assert_eq!(atom_date(""), NO_DATE);
+    }
+}
@@ -5,31 +5,47 @@ cfg_if! { if #[cfg(feature = "ssr")] {
         body::{boxed, Body, BoxBody},
         extract::State,
         response::IntoResponse,
-        http::{Request, Response, StatusCode, Uri},
+        http::{HeaderMap, Request, Response, StatusCode, Uri},
     };
     use axum::response::Response as AxumResponse;
-    use tower::ServiceExt;
+    use axum::middleware;
+    use tower::{ServiceBuilder, ServiceExt};
     use tower_http::services::ServeDir;
     use leptos::*;
     use crate::app::App;
+    use crate::state::AppState;
+    use crate::assets::{cache_headers, CachePolicy};
 
-    pub async fn file_and_error_handler(uri: Uri, State(options): State<LeptosOptions>, req: Request<Body>) -> AxumResponse {
+    pub async fn file_and_error_handler(uri: Uri, State(state): State<AppState>, req: Request<Body>) -> AxumResponse {
+        let options = state.leptos_options.clone();
         let root = options.site_root.clone();
-        let res = get_static_file(uri.clone(), &root).await.unwrap();
+        let res = get_static_file(uri.clone(), req.headers(), &root).await.unwrap();
 
-        if res.status() == StatusCode::OK {
+        // anything but a missing file, e.g. a 304 or a range
+        if res.status() != StatusCode::NOT_FOUND {
             res.into_response()
         } else {
-            let handler = leptos_axum::render_app_to_stream(options.to_owned(), move || view!{<App/>});
+            // the same context as the pages, for the error page's security policy
+            let handler = leptos_axum::render_app_to_stream_with_context(
+                options.to_owned(),
+                move || provide_context(state.clone()),
+                move || view!{<App/>},
+            );
             handler(req).await.into_response()
         }
     }
 
-    async fn get_static_file(uri: Uri, root: &str) -> Result<Response<BoxBody>, (StatusCode, String)> {
-        let req = Request::builder().uri(uri.clone()).body(Body::empty()).unwrap();
+    async fn get_static_file(uri: Uri, headers: &HeaderMap, root: &str) -> Result<Response<BoxBody>, (StatusCode, String)> {
+        let mut req = Request::builder().uri(uri.clone()).body(Body::empty()).unwrap();
+        // for conditional and range requests, and compression
+        *req.headers_mut() = headers.clone();
+
         // `ServeDir` implements `tower::Service` so we can call it with `tower::ServiceExt::oneshot`
         // This path is relative to the cargo root
-        match ServeDir::new(root).oneshot(req).await {
+        let service = ServiceBuilder::new()
+            .layer(middleware::from_fn_with_state(CachePolicy::Revalidate, cache_headers))
+            .service(ServeDir::new(root).precompressed_br().precompressed_gzip());
+        match service.oneshot(req).await {
             Ok(res) => Ok(res.map(boxed)),
             Err(err) => Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -0,0 +1,358 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use web_sys::{IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+
+use crate::api::Image;
+use crate::lightbox::{use_lightbox_href, Lightbox};
+use crate::map::format_location;
+
+#[allow(unused)] // unused in client-side binary
+const PAGE_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GallerySort {
+    /// Newest first.
+    #[default]
+    Date,
+    /// By country and city, newest first within a city.
+    Location,
+}
+
+impl GallerySort {
+    fn from_query(value: Option<&String>) -> GallerySort {
+        match value.map(String::as_str) {
+            Some("location") => GallerySort::Location,
+            _ => GallerySort::Date,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GalleryPage {
+    pub images: Vec<Image>,
+    /// Pass this to get the next page, `None` if this was the last one.
+    pub next: Option<String>,
+}
+
+/// Returns the images that come after `cursor` in `sort` order. Cursors encode the position of
+/// the last image of a page rather than an offset, so uploads and deletions in between don't
+/// shift the following pages.
+#[server(GetGalleryPage, "/api")]
+pub async fn gallery_page(
+    sort: GallerySort,
+    cursor: Option<String>,
+) -> Result<GalleryPage, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let after = cursor
+        .map(|cursor| ssr::Position::decode(&cursor))
+        .transpose()
+        .map_err(|_| ServerFnError::Args("invalid cursor".into()))?;
+
+    Ok(ssr::page(state.public_images(), sort, after.as_ref()))
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use std::cmp::Ordering;
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use serde::{Deserialize, Serialize};
+
+    use super::{GalleryPage, GallerySort, PAGE_SIZE};
+    use crate::api::Image;
+
+    /// The page of `images` that comes after `after` in `sort` order.
+    pub(super) fn page(mut images: Vec<Image>, sort: GallerySort, after: Option<&Position>) -> GalleryPage {
+        images.sort_by(|a, b| Position::of(a).cmp(&Position::of(b), sort));
+
+        let mut images: Vec<Image> = images.into_iter()
+            .filter(|img| after.is_none_or(|after| Position::of(img).cmp(after, sort).is_gt()))
+            .take(PAGE_SIZE + 1)
+            .collect();
+
+        let next = if images.len() > PAGE_SIZE {
+            images.truncate(PAGE_SIZE);
+            images.last().map(|img| Position::of(img).encode())
+        } else {
+            None
+        };
+
+        GalleryPage { images, next }
+    }
+
+    /// Everything needed to tell where an image goes in either sort order.
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Position {
+        timestamp: String,
+        country: String,
+        city: String,
+        id: usize,
+    }
+
+    impl Position {
+        pub fn of(image: &Image) -> Position {
+            Position {
+                timestamp: image.timestamp.clone(),
+                country: image.country.clone(),
+                city: image.city.clone(),
+                id: image.id,
+            }
+        }
+
+        pub fn cmp(&self, other: &Position, sort: GallerySort) -> Ordering {
+            let newest_first = other.timestamp.cmp(&self.timestamp).then(other.id.cmp(&self.id));
+
+            match sort {
+                GallerySort::Date => newest_first,
+                GallerySort::Location => self.country.cmp(&other.country)
+                    .then(self.city.cmp(&other.city))
+                    .then(newest_first),
+            }
+        }
+
+        pub fn encode(&self) -> String {
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("position to serialize"))
+        }
+
+        pub fn decode(cursor: &str) -> Result<Position, Box<dyn std::error::Error>> {
+            Ok(serde_json::from_slice(&URL_SAFE_NO_PAD.decode(cursor)?)?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn image(id: usize, timestamp: &str, country: &str, city: &str) -> Image {
+            Image {
+                id,
+                timestamp: timestamp.into(),
+                country: country.into(),
+                city: city.into(),
+                ..Default::default()
+            }
+        }
+
+        fn ids(images: &[Image]) -> Vec<usize> {
+            images.iter().map(|img| img.id).collect()
+        }
+
+        fn sample() -> Vec<Image> {
+            vec![
+                image(1, "2023-01-01T10:00:00", "Thailand", "Bangkok"),
+                image(2, "2023-03-01T10:00:00", "Japan", "Tokyo"),
+                image(3, "2023-02-01T10:00:00", "Thailand", "Bangkok"),
+                image(4, "2023-02-01T10:00:00", "Thailand", "Chiang Mai"),
+            ]
+        }
+
+        /// All pages, following the cursors.
+        fn all_pages(images: &[Image], sort: GallerySort) -> Vec<usize> {
+            let mut seen = vec![];
+            let mut after = None;
+            loop {
+                let page = page(images.to_vec(), sort, after.as_ref());
+                seen.extend(ids(&page.images));
+                match page.next {
+                    Some(cursor) => after = Some(Position::decode(&cursor).unwrap()),
+                    None => return seen,
+                }
+            }
+        }
+
+        #[test]
+        fn sorts_newest_first() {
+            // same timestamp, the higher id was added later
+            assert_eq!(ids(&page(sample(), GallerySort::Date, None).images), [2, 4, 3, 1]);
+        }
+
+        #[test]
+        fn sorts_by_location() {
+            assert_eq!(ids(&page(sample(), GallerySort::Location, None).images), [2, 3, 1, 4]);
+        }
+
+        #[test]
+        fn pages_through_everything_once() {
+            let images: Vec<Image> = (1..=PAGE_SIZE * 2 + 5)
+                .map(|id| image(id, &format!("2023-01-01T10:{:02}:00", id % 7), "Thailand", "Bangkok"))
+                .collect();
+
+            for sort in [GallerySort::Date, GallerySort::Location] {
+                let mut expected = images.clone();
+                expected.sort_by(|a, b| Position::of(a).cmp(&Position::of(b), sort));
+                assert_eq!(all_pages(&images, sort), ids(&expected));
+            }
+        }
+
+        #[test]
+        fn uploads_dont_shift_later_pages() {
+            let images: Vec<Image> = (1..=PAGE_SIZE + 10)
+                .map(|id| image(id, &format!("2023-01-{:02}T10:00:00", id % 28 + 1), "Thailand", "Bangkok"))
+                .collect();
+
+            let first = page(images.clone(), GallerySort::Date, None);
+            let after = Position::decode(first.next.as_deref().unwrap()).unwrap();
+            let second = page(images.clone(), GallerySort::Date, Some(&after));
+
+            // a newer photo belongs on the first page, which was already seen
+            let mut uploaded = images;
+            uploaded.push(image(1000, "2024-01-01T10:00:00", "Thailand", "Bangkok"));
+            assert_eq!(page(uploaded, GallerySort::Date, Some(&after)), second);
+        }
+
+        #[test]
+        fn rejects_invalid_cursors() {
+            assert!(Position::decode("not a cursor").is_err());
+            assert!(Position::decode(&URL_SAFE_NO_PAD.encode("{}")).is_err());
+        }
+    }
+}
+
+#[component]
+pub fn Gallery() -> impl IntoView {
+    let query = use_query_map();
+    let sort = move || query.with(|q| GallerySort::from_query(q.get("sort")));
+
+    // the first page is rendered on the server, the rest is appended while scrolling
+    let first_page = create_resource(sort, |sort| gallery_page(sort, None));
+    let more = create_rw_signal(Vec::<Image>::new());
+    let next = create_rw_signal(None::<String>);
+
+    let load_more = create_action(move |(sort, cursor): &(GallerySort, String)| {
+        let (sort, cursor) = (*sort, cursor.clone());
+        async move { (sort, gallery_page(sort, Some(cursor)).await) }
+    });
+
+    create_effect(move |_| {
+        if let Some(Ok(page)) = first_page.get() {
+            more.set(vec![]);
+            next.set(page.next);
+        }
+    });
+
+    create_effect(move |_| match load_more.value().get() {
+        // ignore pages that arrive after switching to a different order
+        Some((page_sort, _)) if page_sort != sort() => (),
+        Some((_, Ok(page))) => {
+            more.update(|images| images.extend(page.images));
+            next.set(page.next);
+        }
+        Some((_, Err(e))) => log::error!("failed to load gallery page: {e}"),
+        None => (),
+    });
+
+    let images = Signal::derive(move || {
+        let mut images = match first_page.get() {
+            Some(Ok(page)) => page.images,
+            _ => vec![],
+        };
+        images.extend(more.get());
+        images
+    });
+
+    let on_visible = Callback::new(move |_| {
+        if let Some(cursor) = next.get_untracked() {
+            if !load_more.pending().get_untracked() {
+                load_more.dispatch((sort(), cursor));
+            }
+        }
+    });
+
+    view! {
+        <hgroup>
+            <h1>"Gallery"</h1>
+            <p>
+                "Sort by "
+                <A href="?sort=date" class=move || (sort() != GallerySort::Date).then_some("secondary")>"date"</A>
+                " or "
+                <A href="?sort=location" class=move || (sort() != GallerySort::Location).then_some("secondary")>"location"</A>
+            </p>
+        </hgroup>
+
+        <Transition fallback=|| view! { <progress></progress> }>
+            {move || first_page.get().map(|page| match page {
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                Ok(page) if page.images.is_empty() => view! { <p>"No cats yet."</p> }.into_view(),
+                Ok(page) => view! {
+                    <Lightbox images/>
+                    <div class="gallery">
+                        {page.images.into_iter()
+                            .map(|image| view! { <Thumbnail image/> })
+                            .collect_view()}
+                        <For
+                            each=move || more.get()
+                            key=|image| image.id
+                            children=|image| view! { <Thumbnail image/> }
+                        />
+                    </div>
+                }.into_view(),
+            })}
+        </Transition>
+
+        {move || {
+            // re-created after every page, so it fires again if it's still in view
+            more.with(Vec::len);
+            next.get().is_some().then(|| view! { <ScrollSentinel on_visible/> })
+        }}
+    }
+}
+
+#[component]
+pub fn Thumbnail(image: Image) -> impl IntoView {
+    let href = use_lightbox_href()(image.id);
+    let alt = format!("photo #{} showing one or more cats", image.id);
+    let location = format_location(&image);
+
+    view! {
+        <A href=href>
+            <figure>
+                <img src=image.url_small alt=alt loading="lazy"/>
+                <figcaption>{location}</figcaption>
+            </figure>
+        </A>
+    }
+}
+
+/// Calls `on_visible` when it scrolls into view, or right away if it is already visible.
+#[component]
+fn ScrollSentinel(
+    #[prop(into)]
+    on_visible: Callback<()>,
+    ) -> impl IntoView {
+    let sentinel = create_node_ref::<html::Progress>();
+
+    create_effect(move |_| {
+        let Some(element) = sentinel.get() else {
+            return;
+        };
+
+        let callback = Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            let visible = entries.iter()
+                .any(|entry| entry.unchecked_into::<IntersectionObserverEntry>().is_intersecting());
+            if visible {
+                on_visible(());
+            }
+        });
+
+        let mut options = IntersectionObserverInit::new();
+        // start loading a bit before the end of the page is reached
+        options.root_margin("400px");
+
+        let observer = IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &options)
+            .expect("IntersectionObserver to be supported");
+        observer.observe(&element);
+
+        on_cleanup(move || {
+            observer.disconnect();
+            drop(callback);
+        });
+    });
+
+    view! {
+        <progress node_ref=sentinel></progress>
+    }
+}
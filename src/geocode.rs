@@ -0,0 +1,154 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+
+// Nominatim's usage policy requires an identifying user agent
+const USER_AGENT: &str = concat!("cats-of-asia/", env!("CARGO_PKG_VERSION"));
+
+/// Nominatim's usage policy allows one request a second.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Uploads and the location editor wait for lookups, they shouldn't hang with Nominatim.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// When the last request was sent, held while waiting so requests go out one after the other.
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::const_new(None);
+
+#[derive(Debug, Error)]
+pub enum GeocodeError {
+    #[error("geocoding request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("no place found at {0}, {1}")]
+    NoPlace(f64, f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    pub city: String,
+    pub country: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub label: String,
+    pub place: Place,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Deserialize)]
+struct SearchResponseItem {
+    display_name: String,
+    lat: String,
+    lon: String,
+    address: Option<Address>,
+}
+
+#[derive(Deserialize)]
+struct ReverseResponse {
+    address: Option<Address>,
+}
+
+#[derive(Deserialize)]
+struct Address {
+    city: Option<String>,
+    town: Option<String>,
+    village: Option<String>,
+    country: Option<String>,
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(TIMEOUT)
+            .build()
+            .expect("couldn't build HTTP client")
+    })
+}
+
+/// Waits until Nominatim may be asked again.
+async fn throttle() {
+    let mut last = LAST_REQUEST.lock().await;
+    if let Some(last) = *last {
+        tokio::time::sleep_until(last + MIN_INTERVAL).await;
+    }
+    *last = Some(Instant::now());
+}
+
+/// Looks up the city and country at the given coordinates.
+pub async fn reverse(latitude: f64, longitude: f64) -> Result<Place, GeocodeError> {
+    throttle().await;
+    let response = client()
+        .get(format!("{NOMINATIM_URL}/reverse"))
+        .query(&[
+            ("format", "jsonv2"),
+            ("zoom", "10"),
+            ("accept-language", "en"),
+            ("lat", &latitude.to_string()),
+            ("lon", &longitude.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<ReverseResponse>()
+        .await?;
+
+    let address = response
+        .address
+        .ok_or(GeocodeError::NoPlace(latitude, longitude))?;
+
+    address
+        .into_place()
+        .ok_or(GeocodeError::NoPlace(latitude, longitude))
+}
+
+/// Looks up places matching `query`, e.g. the name of a city.
+pub async fn search(query: &str) -> Result<Vec<SearchResult>, GeocodeError> {
+    throttle().await;
+    let items = client()
+        .get(format!("{NOMINATIM_URL}/search"))
+        .query(&[
+            ("format", "jsonv2"),
+            ("addressdetails", "1"),
+            ("limit", "5"),
+            ("accept-language", "en"),
+            ("q", query),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<SearchResponseItem>>()
+        .await?;
+
+    let results = items
+        .into_iter()
+        .filter_map(|item| {
+            Some(SearchResult {
+                label: item.display_name,
+                place: item.address?.into_place()?,
+                latitude: item.lat.parse().ok()?,
+                longitude: item.lon.parse().ok()?,
+            })
+        })
+        .collect();
+
+    Ok(results)
+}
+
+impl Address {
+    fn into_place(self) -> Option<Place> {
+        let city = self.city.or(self.town).or(self.village).unwrap_or_default();
+        Some(Place {
+            city,
+            country: self.country?,
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+use crate::state::AppState;
+
+/// Liveness: the server answers requests.
+pub async fn healthz_handler() -> impl IntoResponse {
+    "ok"
+}
+
+/// Writes and removes a file in `dir`, as permission bits don't tell whether this process may
+/// write there.
+fn check_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".readyz-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+/// Readiness: the catalog and the media directory can be used, so uploads and page renders will
+/// work. Lists what's wrong otherwise.
+pub async fn readyz_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let mut problems = vec![];
+
+    if let Err(e) = state.catalog.check() {
+        problems.push(format!("catalog: {e}"));
+    }
+
+    if let Err(e) = check_writable(&state.media_dir) {
+        problems.push(format!("media: {e}"));
+    }
+
+    match problems.is_empty() {
+        true => (StatusCode::OK, "ready".to_string()),
+        false => (StatusCode::SERVICE_UNAVAILABLE, problems.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_that_directories_are_writable() {
+        let dir = std::env::temp_dir().join(format!("cats-of-asia-{}-readyz", std::process::id()));
+        assert!(check_writable(&dir).is_err());
+
+        fs::create_dir_all(&dir).unwrap();
+        assert!(check_writable(&dir).is_ok());
+        // the probe is cleaned up
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use exif::{In, Reader, Tag, Value};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::api::Image;
+use crate::catalog::{Catalog, CatalogError};
+use crate::geocode;
+
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+const JPEG_QUALITY: u8 = 85;
+
+/// The resized copies generated for every photo. Only these are ever served, the uploaded
+/// original is kept for reprocessing.
+#[derive(Clone, Copy, Debug)]
+pub enum Variant {
+    Large,
+    Medium,
+    Small,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 3] = [Variant::Large, Variant::Medium, Variant::Small];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Large => "large",
+            Variant::Medium => "medium",
+            Variant::Small => "small",
+        }
+    }
+
+    fn max_size(&self) -> u32 {
+        match self {
+            Variant::Large => 2048,
+            Variant::Medium => 1024,
+            Variant::Small => 400,
+        }
+    }
+
+    pub fn path(&self, media_dir: &Path, sha256: &str) -> PathBuf {
+        media_dir.join(self.name()).join(format!("{sha256}.jpg"))
+    }
+
+    pub fn url(&self, sha256: &str) -> String {
+        format!("/media/{}/{sha256}.jpg", self.name())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IngestError {
+    #[error("this photo is already in the catalog as #{0}")]
+    Duplicate(usize),
+    #[error("not a supported image: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("failed to store image: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Catalog(#[from] CatalogError),
+}
+
+/// What is learned about a photo while processing it, mostly from its EXIF data.
+#[derive(Debug, Default)]
+struct Metadata {
+    taken_at: Option<NaiveDateTime>,
+    coordinates: Option<(f64, f64)>,
+    orientation: u32,
+    dhash: u64,
+}
+
+/// Runs an uploaded photo through the whole pipeline: dedupe by sha256, EXIF extraction,
+/// generating the variants, reverse geocoding and finally adding it to the catalog.
+///
+/// Photos without GPS data are added with `needs_location` set, so they stay hidden until an
+/// admin places them on the map.
+pub async fn ingest(
+    catalog: &Catalog,
+    media_dir: &Path,
+    data: Vec<u8>,
+) -> Result<Image, IngestError> {
+    let sha256 = hex_digest(&data);
+
+    // saves processing it, `insert` checks again in case the same photo is uploaded twice at once
+    if let Some(existing) = catalog.find_by_sha256(&sha256) {
+        return Err(IngestError::Duplicate(existing.id));
+    }
+
+    let metadata = {
+        let media_dir = media_dir.to_path_buf();
+        let sha256 = sha256.clone();
+
+        tokio::task::spawn_blocking(move || process(&media_dir, &sha256, &data))
+            .await
+            .expect("image processing task panicked")?
+    };
+
+    let timestamp = metadata
+        .taken_at
+        .unwrap_or_else(|| Utc::now().naive_utc())
+        .format(TIMESTAMP_FORMAT)
+        .to_string();
+
+    let mut image = Image {
+        id: 0,
+        url_large: Variant::Large.url(&sha256),
+        url_medium: Variant::Medium.url(&sha256),
+        url_small: Variant::Small.url(&sha256),
+        sha256,
+        dhash: Some(format_dhash(metadata.dhash)),
+        timestamp,
+        latitude: 0.0,
+        longitude: 0.0,
+        city: String::new(),
+        country: String::new(),
+        needs_location: true,
+        hide_location: false,
+        tags: vec![],
+        caption: None,
+        cat_ids: vec![],
+    };
+
+    if let Some((latitude, longitude)) = metadata.coordinates {
+        locate(&mut image, latitude, longitude).await;
+    }
+
+    match catalog.insert(image) {
+        Ok(image) => Ok(image),
+        Err(CatalogError::Duplicate(id)) => Err(IngestError::Duplicate(id)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Sets the coordinates of `image` and fills in city and country via reverse geocoding.
+///
+/// A failed lookup is not fatal, the photo just shows up without a place name.
+pub async fn locate(image: &mut Image, latitude: f64, longitude: f64) {
+    image.latitude = latitude;
+    image.longitude = longitude;
+    image.needs_location = false;
+
+    match geocode::reverse(latitude, longitude).await {
+        Ok(place) => {
+            image.city = place.city;
+            image.country = place.country;
+        }
+        Err(e) => log::warn!("failed to geocode photo {}: {e}", image.sha256),
+    }
+}
+
+/// Deletes the original and all variants of a photo.
+pub fn remove_files(media_dir: &Path, sha256: &str) -> io::Result<()> {
+    let paths = Variant::ALL
+        .iter()
+        .map(|variant| variant.path(media_dir, sha256))
+        .chain([media_dir.join("originals").join(sha256)]);
+
+    for path in paths {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the difference hash of an image: each bit says whether a pixel of a 9x8 grayscale
+/// thumbnail is brighter than its right neighbour. Resizing or re-encoding a photo barely
+/// changes it, so similar photos have hashes with a small hamming distance.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    hash
+}
+
+/// Computes the difference hash of an image file, for photos ingested before hashes were stored.
+pub fn dhash_file(path: &Path) -> Result<u64, IngestError> {
+    Ok(dhash(&image::open(path)?))
+}
+
+pub fn format_dhash(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
+pub fn parse_dhash(hash: &str) -> Option<u64> {
+    u64::from_str_radix(hash, 16).ok()
+}
+
+fn process(media_dir: &Path, sha256: &str, data: &[u8]) -> Result<Metadata, IngestError> {
+    let mut metadata = read_metadata(data);
+    let original = image::load_from_memory(data)?;
+    let original = apply_orientation(original, metadata.orientation);
+    metadata.dhash = dhash(&original);
+
+    let originals = media_dir.join("originals");
+    fs::create_dir_all(&originals)?;
+    fs::write(originals.join(sha256), data)?;
+
+    for variant in Variant::ALL {
+        let path = variant.path(media_dir, sha256);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let size = variant.max_size();
+        let resized = if original.width() > size || original.height() > size {
+            original.resize(size, size, FilterType::Lanczos3)
+        } else {
+            original.clone()
+        };
+
+        // re-encoding from pixels means none of the original EXIF data ends up in the variant
+        let mut out = vec![];
+        JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+            .encode_image(&DynamicImage::ImageRgb8(resized.to_rgb8()))?;
+        fs::write(path, out)?;
+    }
+
+    Ok(metadata)
+}
+
+fn read_metadata(data: &[u8]) -> Metadata {
+    let exif = match Reader::new().read_from_container(&mut Cursor::new(data)) {
+        Ok(exif) => exif,
+        Err(_) => return Metadata::default(),
+    };
+
+    let taken_at = exif
+        .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .or_else(|| exif.get_field(Tag::DateTime, In::PRIMARY))
+        .and_then(|field| match &field.value {
+            Value::Ascii(values) => values.first(),
+            _ => None,
+        })
+        .and_then(|ascii| exif::DateTime::from_ascii(ascii).ok())
+        .and_then(|dt| {
+            NaiveDate::from_ymd_opt(dt.year.into(), dt.month.into(), dt.day.into())?
+                .and_hms_opt(dt.hour.into(), dt.minute.into(), dt.second.into())
+        });
+
+    let latitude = gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S');
+    let longitude = gps_coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W');
+
+    let orientation = exif
+        .get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(1);
+
+    Metadata {
+        taken_at,
+        coordinates: latitude.zip(longitude),
+        orientation,
+        dhash: 0,
+    }
+}
+
+/// Converts a degrees/minutes/seconds GPS field into decimal degrees, negated if the reference
+/// field says it is on the southern or western hemisphere.
+fn gps_coordinate(exif: &exif::Exif, tag: Tag, ref_tag: Tag, negative_ref: u8) -> Option<f64> {
+    let dms = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(dms) if dms.len() == 3 => dms.clone(),
+        _ => return None,
+    };
+
+    let degrees = dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0;
+    if !degrees.is_finite() {
+        return None;
+    }
+
+    let negative = match &exif.get_field(ref_tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().and_then(|v| v.first()) == Some(&negative_ref),
+        _ => false,
+    };
+
+    Some(if negative { -degrees } else { degrees })
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn hex_digest(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    /// Gets brighter or darker from left to right.
+    fn gradient(width: u32, height: u32, brighter: bool) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            Luma([if brighter { value } else { 255 - value }])
+        }))
+    }
+
+    #[test]
+    fn dhash_compares_neighbours() {
+        // every pixel is brighter than its right neighbour
+        assert_eq!(dhash(&gradient(90, 80, false)), u64::MAX);
+        assert_eq!(dhash(&gradient(90, 80, true)), 0);
+    }
+
+    #[test]
+    fn dhash_survives_resizing() {
+        let original = gradient(640, 480, false);
+        let resized = original.resize_exact(200, 150, FilterType::Lanczos3);
+
+        assert!((dhash(&original) ^ dhash(&resized)).count_ones() <= 2);
+    }
+
+    #[test]
+    fn dhash_round_trips_as_hex() {
+        for hash in [0, 1, 0xdead_beef, u64::MAX] {
+            assert_eq!(format_dhash(hash).len(), 16);
+            assert_eq!(parse_dhash(&format_dhash(hash)), Some(hash));
+        }
+        assert_eq!(parse_dhash("not a hash"), None);
+    }
+}
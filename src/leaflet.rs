@@ -1,8 +1,9 @@
 use serde::{Serialize, Deserialize};
 use wasm_bindgen::prelude::*;
-use serde_wasm_bindgen::to_value;
+use serde_wasm_bindgen::{from_value, to_value};
 
 use crate::api::Image;
+use crate::map::MapSettings;
 
 #[wasm_bindgen]
 extern "C" {
@@ -23,6 +24,12 @@ extern "C" {
     #[wasm_bindgen(static_method_of = L)]
     pub fn circle(center: JsValue, options: JsValue) -> Circle;
 
+    #[wasm_bindgen(static_method_of = L)]
+    pub fn marker(center: JsValue, options: JsValue) -> Marker;
+
+    #[wasm_bindgen(static_method_of = L)]
+    pub fn polyline(latlngs: JsValue, options: JsValue) -> Polyline;
+
     type TileLayer;
 
     #[wasm_bindgen(method)]
@@ -37,6 +44,12 @@ extern "C" {
     #[wasm_bindgen(method)]
     fn remove(this: &Map);
 
+    #[wasm_bindgen(method)]
+    fn on(this: &Map, event: &str, handler: &js_sys::Function);
+
+    #[wasm_bindgen(method)]
+    fn fitBounds(this: &Map, bounds: JsValue, options: JsValue);
+
     type Circle;
 
     #[wasm_bindgen(method)]
@@ -44,6 +57,26 @@ extern "C" {
 
     #[wasm_bindgen(method)]
     pub fn addTo(this: &Circle, map: Map);
+
+    #[derive(Clone, Debug)]
+    pub type Marker;
+
+    #[wasm_bindgen(method)]
+    pub fn addTo(this: &Marker, map: Map);
+
+    #[wasm_bindgen(method)]
+    fn setLatLng(this: &Marker, center: JsValue);
+
+    #[wasm_bindgen(method)]
+    fn getLatLng(this: &Marker) -> JsValue;
+
+    #[wasm_bindgen(method, js_name = on)]
+    fn on_marker(this: &Marker, event: &str, handler: &js_sys::Function);
+
+    type Polyline;
+
+    #[wasm_bindgen(method)]
+    pub fn addTo(this: &Polyline, map: Map);
 }
 
 #[derive(Serialize, Deserialize)]
@@ -51,6 +84,9 @@ struct MapOptions {
     #[serde(rename="maxZoom")]
     pub max_zoom: u8,
     pub id: String,
+    #[serde(rename="accessToken")]
+    pub access_token: String,
+    pub attribution: String,
     #[serde(rename="tileSize")]
     pub tile_size: u32,
     #[serde(rename="zoomOffset")]
@@ -63,28 +99,57 @@ struct CircleOptions {
 //    pub color: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct MarkerOptions {
+    pub draggable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PolylineOptions {
+    pub color: String,
+    pub weight: u8,
+    #[serde(rename="dashArray")]
+    pub dash_array: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FitBoundsOptions {
+    #[serde(rename="maxZoom")]
+    pub max_zoom: u8,
+    pub padding: (u16, u16),
+}
+
+#[derive(Deserialize)]
+struct LatLng {
+    lat: f64,
+    lng: f64,
+}
+
 #[derive(Clone)]
 pub struct LeafletMap {
     map: Map,
 }
 
 impl LeafletMap {
-    pub fn new(access_token: &str, max_zoom: u8) -> LeafletMap {
-        let url = format!("https://api.mapbox.com/styles/v1/{{id}}/tiles/{{z}}/{{x}}/{{y}}?access_token={access_token}");
-        let map = L::map("cattos");
+    pub fn new(element_id: &str, settings: &MapSettings) -> LeafletMap {
+        let map = L::map(element_id);
 
         let options = MapOptions{
-            max_zoom,
-            id: "mapbox/streets-v11".into(),
-            tile_size: 512,
-            zoom_offset: -1,
+            max_zoom: settings.max_zoom,
+            id: settings.tile_id.clone(),
+            access_token: settings.access_token.clone(),
+            attribution: settings.attribution.clone(),
+            tile_size: settings.tile_size,
+            zoom_offset: settings.zoom_offset,
         };
 
         let options = to_value(&options).expect("static value to convert successfully");
-        let tile_layer = L::tileLayer(&url, options);
+        let tile_layer = L::tileLayer(&settings.tile_url, options);
         tile_layer.addTo(map.clone());
 
-        LeafletMap{map}
+        let map = LeafletMap{map};
+        map.set_view(settings.center.0, settings.center.1, settings.zoom);
+        map
     }
 
     pub fn get_map(&self) -> &Map {
@@ -100,6 +165,7 @@ impl LeafletMap {
     pub fn add_marker(
         &self,
         image: &Image,
+        cat_names: &[String],
         radius: u8,
         ) {
         let center = vec![image.latitude, image.longitude];
@@ -112,11 +178,98 @@ impl LeafletMap {
         let options = to_value(&options).expect("static value to convert successfully");
         let circle = L::circle(center, options);
         let image = to_value(image).expect("Image struct to convert to a JS object");
+        let cat_names = to_value(cat_names).expect("cat names to convert to a JS array");
+        js_sys::Reflect::set(&image, &"catNames".into(), &cat_names)
+            .expect("image to be a JS object");
 
         bindPopup(circle.clone(), image, self.map.clone());
         circle.addTo(self.map.clone());
     }
 
+    /// Calls `handler` with the latitude and longitude of every click on the map.
+    pub fn on_click(&self, mut handler: impl FnMut(f64, f64) + 'static) {
+        let closure = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            let latlng = js_sys::Reflect::get(&event, &"latlng".into())
+                .expect("click event to have a latlng property");
+            let latlng: LatLng = from_value(latlng).expect("latlng to convert successfully");
+            handler(latlng.lat, latlng.lng);
+        });
+
+        self.map.on("click", closure.as_ref().unchecked_ref());
+        // the handler has to live as long as the map, which we don't track
+        closure.forget();
+    }
+
+    pub fn add_pin(&self, latitude: f64, longitude: f64) -> Marker {
+        self.make_pin(latitude, longitude, false)
+    }
+
+    /// Adds a pin that can be dragged around, calling `on_drag` with its new position.
+    pub fn add_draggable_pin(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        mut on_drag: impl FnMut(f64, f64) + 'static,
+        ) -> Marker {
+        let marker = self.make_pin(latitude, longitude, true);
+
+        let dragged = marker.clone();
+        let closure = Closure::<dyn FnMut(JsValue)>::new(move |_| {
+            let latlng: LatLng = from_value(dragged.getLatLng())
+                .expect("latlng to convert successfully");
+            on_drag(latlng.lat, latlng.lng);
+        });
+
+        marker.on_marker("dragend", closure.as_ref().unchecked_ref());
+        // the handler has to live as long as the marker, which we don't track
+        closure.forget();
+        marker
+    }
+
+    fn make_pin(&self, latitude: f64, longitude: f64, draggable: bool) -> Marker {
+        let center = vec![latitude, longitude];
+        let center = to_value(&center).expect("f64 to convert successfully");
+        let options = to_value(&MarkerOptions{draggable}).expect("static value to convert successfully");
+        let marker = L::marker(center, options);
+        marker.addTo(self.map.clone());
+        marker
+    }
+
+    pub fn move_pin(&self, marker: &Marker, latitude: f64, longitude: f64) {
+        let center = vec![latitude, longitude];
+        let center = to_value(&center).expect("f64 to convert successfully");
+        marker.setLatLng(center);
+    }
+
+    /// Draws a dashed line through `points`, in order.
+    pub fn add_path(&self, points: &[(f64, f64)]) {
+        let latlngs = points.iter().map(|(lat, lng)| vec![*lat, *lng]).collect::<Vec<_>>();
+        let latlngs = to_value(&latlngs).expect("f64 to convert successfully");
+
+        let options = PolylineOptions{
+            color: "#1095c1".into(),
+            weight: 3,
+            dash_array: "6 6".into(),
+        };
+
+        let options = to_value(&options).expect("static value to convert successfully");
+        L::polyline(latlngs, options).addTo(self.map.clone());
+    }
+
+    /// Zooms and pans the map so that all `points` are visible, but never further in than
+    /// `max_zoom`. Leaves the map as it is without points, Leaflet can't fit nothing.
+    pub fn fit_points(&self, points: &[(f64, f64)], max_zoom: u8) {
+        if points.is_empty() {
+            return;
+        }
+        let bounds = points.iter().map(|(lat, lng)| vec![*lat, *lng]).collect::<Vec<_>>();
+        let bounds = to_value(&bounds).expect("f64 to convert successfully");
+
+        let options = to_value(&FitBoundsOptions{max_zoom, padding: (20, 20)})
+            .expect("static value to convert successfully");
+        self.map.fitBounds(bounds, options);
+    }
+
     pub fn remove(&self) {
         removeMap(self.map.clone())
     }
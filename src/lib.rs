@@ -6,6 +6,45 @@ pub mod leaflet;
 pub mod api;
 pub mod map;
 pub mod favorites;
+pub mod favorites_file;
+pub mod favorites_store;
+pub mod admin;
+pub mod location_editor;
+pub mod duplicates;
+pub mod photo_editor;
+pub mod search;
+pub mod cats;
+pub mod photo;
+pub mod cat_profile;
+pub mod gallery;
+pub mod lightbox;
+pub mod places;
+pub mod stats;
+pub mod social;
+pub mod collections;
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    pub mod assets;
+    pub mod audit;
+    pub mod auth;
+    pub mod catalog;
+    pub mod collection_store;
+    pub mod config;
+    pub mod export;
+    pub mod feed;
+    pub mod geocode;
+    pub mod health;
+    pub mod ingest;
+    pub mod metrics;
+    pub mod privacy;
+    pub mod rate_limit;
+    pub mod security;
+    pub mod seo;
+    pub mod server_fns;
+    pub mod shutdown;
+    pub mod state;
+    pub mod telemetry;
+}}
 
 cfg_if! { if #[cfg(feature = "hydrate")] {
     use leptos::*;
@@ -0,0 +1,148 @@
+use leptos::*;
+use leptos_router::*;
+
+use crate::api::{Cat, Image, ImagesResource};
+use crate::cats::{cat_names, CatsResource};
+use crate::map::format_location;
+
+/// The query parameter holding the id of the photo shown in the lightbox, so the URL of an open
+/// lightbox can be shared.
+pub(crate) const PHOTO_PARAM: &str = "photo";
+
+/// Minimum horizontal distance in pixels for a touch to count as a swipe.
+const SWIPE_DISTANCE: i32 = 50;
+
+/// Returns a function that builds the URL of the current page with the lightbox opened at a photo.
+pub fn use_lightbox_href() -> impl Fn(usize) -> String + Copy {
+    let location = use_location();
+
+    move |id: usize| {
+        let mut query = location.query.get_untracked();
+        query.insert(PHOTO_PARAM.into(), id.to_string());
+        format!("{}{}", location.pathname.get_untracked(), query.to_query_string())
+    }
+}
+
+/// Shows the photo selected by the `photo` query parameter on top of the page. `images` is what
+/// previous and next step through, e.g. the results of a search.
+#[component]
+pub fn Lightbox(#[prop(into)] images: Signal<Vec<Image>>) -> impl IntoView {
+    let all_images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let cats = use_context::<CatsResource>().expect("it to have been loaded in App");
+    let location = use_location();
+    let query = use_query_map();
+    let href = use_lightbox_href();
+
+    let current_id = move || query.with(|q| q.get(PHOTO_PARAM).and_then(|id| id.parse::<usize>().ok()));
+
+    // a shared link may point to a photo that isn't part of the context, e.g. because the
+    // favorites of whoever opened it differ, so fall back to all photos
+    let current = move || {
+        let id = current_id()?;
+        images.with(|images| images.iter().find(|img| img.id == id).cloned())
+            .or_else(|| all_images.0.get()?.into_iter().find(|img| img.id == id))
+    };
+
+    let neighbour = move |offset: isize| {
+        let id = current_id()?;
+        images.with(|images| {
+            let index = images.iter().position(|img| img.id == id)?;
+            images.get(index.checked_add_signed(offset)?).map(|img| img.id)
+        })
+    };
+
+    let navigate = store_value(use_navigate());
+    let go = move |href: String| navigate.with_value(|navigate| {
+        navigate(&href, NavigateOptions { replace: true, ..Default::default() })
+    });
+
+    let show = move |offset| {
+        if let Some(id) = neighbour(offset) {
+            go(href(id));
+        }
+    };
+
+    let close = move || {
+        let mut query = location.query.get_untracked();
+        query.remove(PHOTO_PARAM);
+        go(format!("{}{}", location.pathname.get_untracked(), query.to_query_string()));
+    };
+
+    let keys = window_event_listener(ev::keydown, move |ev| {
+        if current_id().is_none() {
+            return;
+        }
+        match ev.key().as_str() {
+            "ArrowLeft" => show(-1),
+            "ArrowRight" => show(1),
+            "Escape" => close(),
+            _ => (),
+        }
+    });
+    on_cleanup(move || keys.remove());
+
+    let touch_start = store_value(None::<i32>);
+    let on_touch_start = move |ev: ev::TouchEvent| {
+        touch_start.set_value(ev.changed_touches().get(0).map(|touch| touch.client_x()));
+    };
+    let on_touch_end = move |ev: ev::TouchEvent| {
+        let start = touch_start.get_value();
+        let end = ev.changed_touches().get(0).map(|touch| touch.client_x());
+        if let (Some(start), Some(end)) = (start, end) {
+            if end - start > SWIPE_DISTANCE {
+                show(-1);
+            } else if start - end > SWIPE_DISTANCE {
+                show(1);
+            }
+        }
+    };
+
+    view! {
+        {move || current().map(|image| {
+            let has_previous = neighbour(-1).is_some();
+            let has_next = neighbour(1).is_some();
+
+            view! {
+                <div
+                    class="lightbox"
+                    on:click=move |_| close()
+                    on:touchstart=on_touch_start
+                    on:touchend=on_touch_end
+                >
+                    <button class="lightbox-close" on:click=move |_| close()>"×"</button>
+                    <button
+                        class="lightbox-previous"
+                        disabled=!has_previous
+                        on:click=move |ev| { ev.stop_propagation(); show(-1) }
+                    >"‹"</button>
+                    <figure on:click=|ev| ev.stop_propagation()>
+                        <img src=image.url_large.clone() alt=format!("photo #{} showing one or more cats", image.id)/>
+                        <LightboxCaption image cats=cats.0.get().unwrap_or_default()/>
+                    </figure>
+                    <button
+                        class="lightbox-next"
+                        disabled=!has_next
+                        on:click=move |ev| { ev.stop_propagation(); show(1) }
+                    >"›"</button>
+                </div>
+            }
+        })}
+    }
+}
+
+#[component]
+fn LightboxCaption(image: Image, cats: Vec<Cat>) -> impl IntoView {
+    let date = image.timestamp.split('T').next().unwrap_or_default().to_string();
+    let location = format_location(&image);
+    let names = cat_names(&cats, &image.cat_ids).join(", ");
+    let details = format!("/photos/{}", image.id);
+
+    view! {
+        <figcaption>
+            {image.caption.map(|caption| view! { <strong>{caption}</strong><br/> })}
+            <A href=details>"Photo #"{image.id}</A>
+            ". Taken on "{date}" in "{location}"."
+            {(!names.is_empty()).then(|| view! { <br/>"With "{names} })}
+        </figcaption>
+    }
+}
@@ -0,0 +1,332 @@
+use leptos::*;
+use leptos_meta::*;
+use serde::{Deserialize, Serialize};
+
+use crate::admin::AdminNav;
+use crate::api::Image;
+use crate::leaflet::{LeafletMap, Marker};
+use crate::map::create_map;
+
+const MAP_ELEMENT_ID: &str = "location-editor-map";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub city: String,
+    pub country: String,
+}
+
+impl From<&Image> for Location {
+    fn from(image: &Image) -> Self {
+        Location {
+            latitude: image.latitude,
+            longitude: image.longitude,
+            city: image.city.clone(),
+            country: image.country.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlaceSuggestion {
+    pub label: String,
+    pub location: Location,
+}
+
+#[server(MissingLocations, "/admin/api")]
+pub async fn missing_locations() -> Result<Vec<Image>, ServerFnError> {
+    crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    Ok(state.catalog.images().into_iter().filter(|img| img.needs_location).collect())
+}
+
+#[server(SearchPlaces, "/admin/api")]
+pub async fn search_places(query: String) -> Result<Vec<PlaceSuggestion>, ServerFnError> {
+    crate::auth::require_admin().await?;
+
+    let results = crate::geocode::search(&query).await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    Ok(results.into_iter()
+        .map(|result| PlaceSuggestion {
+            label: result.label,
+            location: Location {
+                latitude: result.latitude,
+                longitude: result.longitude,
+                city: result.place.city,
+                country: result.place.country,
+            },
+        })
+        .collect())
+}
+
+#[server(LookupPlace, "/admin/api")]
+pub async fn lookup_place(latitude: f64, longitude: f64) -> Result<Location, ServerFnError> {
+    crate::auth::require_admin().await?;
+
+    let place = crate::geocode::reverse(latitude, longitude).await
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    Ok(Location { latitude, longitude, city: place.city, country: place.country })
+}
+
+#[server(SaveLocation, "/admin/api")]
+pub async fn save_location(
+    id: usize,
+    latitude: f64,
+    longitude: f64,
+    city: String,
+    country: String,
+) -> Result<Image, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    store_location(&state, &user, id, Location { latitude, longitude, city, country })
+}
+
+/// Rejects what isn't a point on earth. JSON has no NaN, a catalog with one couldn't be read back.
+#[cfg(feature = "ssr")]
+pub(crate) fn check_coordinates(latitude: f64, longitude: f64) -> Result<(), ServerFnError> {
+    if !latitude.is_finite() || !longitude.is_finite()
+        || !(-90.0..=90.0).contains(&latitude)
+        || !(-180.0..=180.0).contains(&longitude)
+    {
+        return Err(ServerFnError::ServerError(format!("{latitude}, {longitude} is not a location")));
+    }
+    Ok(())
+}
+
+/// Moves an image to `location`, publishes it and records the change in the audit log.
+#[cfg(feature = "ssr")]
+pub(crate) fn store_location(
+    state: &crate::state::AppState,
+    user: &str,
+    id: usize,
+    location: Location,
+) -> Result<Image, ServerFnError> {
+    check_coordinates(location.latitude, location.longitude)?;
+
+    let before = state.catalog.get(id)
+        .ok_or_else(|| ServerFnError::ServerError(format!("no image with id {id}")))?;
+
+    let after = state.catalog
+        .update(id, |img| {
+            img.latitude = location.latitude;
+            img.longitude = location.longitude;
+            img.city = location.city.clone();
+            img.country = location.country.clone();
+            img.needs_location = false;
+        })
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    state.audit
+        .record(user, id, "set_location", Location::from(&before), &location)
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    Ok(after)
+}
+
+#[component]
+pub fn AdminLocations() -> impl IntoView {
+    let save = create_server_action::<SaveLocation>();
+    let images = create_resource(move || save.version().get(), |_| missing_locations());
+    let (selected, set_selected) = create_signal(None::<Image>);
+
+    create_effect(move |_| {
+        if let Some(Ok(_)) = save.value().get() {
+            set_selected(None);
+        }
+    });
+
+    view! {
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
+
+        <AdminNav/>
+        <h1>"Photos without location"</h1>
+        <Transition fallback=|| view! { <progress></progress> }>
+            <div class="location-thumbs">
+                {move || images.get().map(|images| match images {
+                    Err(e) => view! { <p class="upload-error">{e.to_string()}</p> }.into_view(),
+                    Ok(images) if images.is_empty() => view! {
+                        <p>"Every photo has a location."</p>
+                    }.into_view(),
+                    Ok(images) => images.into_iter()
+                        .map(|image| {
+                            let url_small = image.url_small.clone();
+                            let alt = format!("photo #{} without a location", image.id);
+                            view! {
+                                <a on:click=move |_| set_selected(Some(image.clone()))>
+                                    <img src=url_small alt=alt/>
+                                </a>
+                            }
+                        })
+                        .collect_view(),
+                })}
+            </div>
+        </Transition>
+
+        {move || selected().map(|image| view! { <LocationEditor image save/> })}
+    }
+}
+
+#[component]
+fn LocationEditor(
+    image: Image,
+    save: Action<SaveLocation, Result<Image, ServerFnError>>,
+) -> impl IntoView {
+    let coordinates = create_rw_signal(None::<(f64, f64)>);
+    let city = create_rw_signal(image.city.clone());
+    let country = create_rw_signal(image.country.clone());
+    let (query, set_query) = create_signal(String::new());
+
+    let lookup = create_server_action::<LookupPlace>();
+    let search = create_server_action::<SearchPlaces>();
+    let pin = store_value(None::<Marker>);
+
+    let map = create_local_resource(
+        || (),
+        |_| create_map(MAP_ELEMENT_ID));
+
+    on_cleanup(move || {
+        if let Some(map) = map() {
+            map.remove();
+        }
+    });
+
+    create_effect(move |_| {
+        if let Some(Ok(location)) = lookup.value().get() {
+            city.set(location.city);
+            country.set(location.country);
+        }
+    });
+
+    let on_moved = move |latitude, longitude| {
+        coordinates.set(Some((latitude, longitude)));
+        lookup.dispatch(LookupPlace { latitude, longitude });
+    };
+
+    let place_pin = move |map: &LeafletMap, latitude, longitude| {
+        pin.update_value(|pin| match pin {
+            Some(pin) => map.move_pin(pin, latitude, longitude),
+            None => *pin = Some(map.add_draggable_pin(latitude, longitude, on_moved)),
+        });
+    };
+
+    create_effect(move |_| {
+        if let Some(map) = map() {
+            let clicked = map.clone();
+            map.on_click(move |latitude, longitude| {
+                place_pin(&clicked, latitude, longitude);
+                on_moved(latitude, longitude);
+            });
+        }
+    });
+
+    let pick_suggestion = move |location: Location| {
+        if let Some(map) = map() {
+            map.set_view(location.latitude, location.longitude, 13);
+            place_pin(&map, location.latitude, location.longitude);
+        }
+        coordinates.set(Some((location.latitude, location.longitude)));
+        city.set(location.city);
+        country.set(location.country);
+    };
+
+    let suggestions = move || match search.value().get() {
+        Some(Ok(suggestions)) => suggestions.into_iter()
+            .map(|suggestion| {
+                let label = suggestion.label.clone();
+                view! {
+                    <li>
+                        <a on:click=move |_| pick_suggestion(suggestion.location.clone())>{label}</a>
+                    </li>
+                }
+            })
+            .collect_view(),
+        Some(Err(e)) => view! { <li class="upload-error">{e.to_string()}</li> }.into_view(),
+        None => ().into_view(),
+    };
+
+    let id = image.id;
+    let on_save = move |_| {
+        if let Some((latitude, longitude)) = coordinates.get_untracked() {
+            save.dispatch(SaveLocation {
+                id,
+                latitude,
+                longitude,
+                city: city.get_untracked(),
+                country: country.get_untracked(),
+            });
+        }
+    };
+
+    let alt = format!("photo #{id} without a location");
+
+    view! {
+        <article>
+            <header>"Photo #"{id}</header>
+            <img src=image.url_medium alt=alt class="location-preview"/>
+
+            <form on:submit=move |ev| {
+                ev.prevent_default();
+                search.dispatch(SearchPlaces { query: query.get_untracked() });
+            }>
+                <input
+                    type="search"
+                    placeholder="Search for a city"
+                    prop:value=query
+                    on:input=move |ev| set_query(event_target_value(&ev))
+                />
+            </form>
+            <ul>{suggestions}</ul>
+
+            <div id=MAP_ELEMENT_ID class="location-picker"></div>
+
+            <div class="grid">
+                <input
+                    placeholder="City"
+                    prop:value=move || city.get()
+                    on:input=move |ev| city.set(event_target_value(&ev))
+                />
+                <input
+                    placeholder="Country"
+                    prop:value=move || country.get()
+                    on:input=move |ev| country.set(event_target_value(&ev))
+                />
+            </div>
+
+            <button disabled=move || coordinates().is_none() on:click=on_save>"Save"</button>
+            {move || save.value().get().and_then(Result::err).map(|e| view! {
+                <p class="upload-error">{e.to_string()}</p>
+            })}
+        </article>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_points_on_earth() {
+        for (latitude, longitude) in [(13.7563, 100.5018), (-90.0, -180.0), (90.0, 180.0), (0.0, 0.0)] {
+            assert!(check_coordinates(latitude, longitude).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for (latitude, longitude) in [
+            (f64::NAN, 100.0),
+            (13.0, f64::INFINITY),
+            (90.5, 100.0),
+            (13.0, -180.5),
+        ] {
+            assert!(check_coordinates(latitude, longitude).is_err());
+        }
+    }
+}
@@ -1,13 +1,43 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::{routing::post, Router};
+    use axum::{
+        extract::DefaultBodyLimit,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use cats_of_asia::admin::upload_handler;
+    use cats_of_asia::api::images_handler;
+    use cats_of_asia::assets::{cache_headers, precompress, CachePolicy};
     use cats_of_asia::app::*;
+    use cats_of_asia::audit::AuditLog;
+    use cats_of_asia::auth::admin_guard;
+    use cats_of_asia::catalog::Catalog;
+    use cats_of_asia::collection_store::CollectionStore;
+    use cats_of_asia::config::Config;
+    use cats_of_asia::export::{geojson_handler, gpx_handler, kml_handler};
+    use cats_of_asia::feed::{atom_handler, rss_handler};
     use cats_of_asia::fileserv::file_and_error_handler;
+    use cats_of_asia::health::{healthz_handler, readyz_handler};
+    use cats_of_asia::ingest::Variant;
+    use cats_of_asia::metrics::{metrics_handler, track_metrics, Metrics};
+    use cats_of_asia::privacy::scrub_variants;
+    use cats_of_asia::rate_limit::{rate_limit, RateLimiter};
+    use cats_of_asia::security::security_headers;
+    use cats_of_asia::seo::{robots_handler, sitemap_handler};
+    use cats_of_asia::server_fns::server_fn_handler;
+    use cats_of_asia::shutdown;
+    use cats_of_asia::state::AppState;
+    use cats_of_asia::telemetry;
     use leptos::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
+    use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+    use tower_http::services::ServeDir;
+    use tower_http::trace::{DefaultOnResponse, TraceLayer};
 
-    simple_logger::init_with_level(log::Level::Debug).expect("couldn't initialize logging");
+    // phone cameras easily produce 10MB per photo and uploads come in batches
+    const MAX_UPLOAD_SIZE: usize = 512 * 1024 * 1024;
 
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
@@ -19,20 +49,116 @@ async fn main() {
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(App);
 
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            // there is nowhere else to log it to yet
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    telemetry::init(&config.logging);
+
+    let shutdown_timeout = std::time::Duration::from_secs(config.shutdown_timeout);
+    let admin = config.admin_credentials();
+    if admin.is_none() {
+        log::warn!("no admin credentials configured, admin area is disabled");
+    }
+    if config.public_url.is_none() {
+        log::warn!("no public_url configured, absolute links are built from the Host header");
+    }
+
+    // a fresh installation has no photos yet, uploads create the rest
+    if let Err(e) = std::fs::create_dir_all(&config.media_dir) {
+        log::error!("couldn't create media directory {}: {e}", config.media_dir.display());
+        std::process::exit(1);
+    }
+
+    let state = AppState {
+        leptos_options,
+        catalog: Catalog::open(&config.catalog_path).expect("couldn't open catalog"),
+        collections: CollectionStore::open(&config.collections_path).expect("couldn't open collections"),
+        audit: AuditLog::new(&config.audit_log_path),
+        media_dir: config.media_dir,
+        public_url: config.public_url,
+        trust_proxy: config.trust_proxy,
+        map: config.map,
+        admin,
+        privacy: config.privacy,
+        robots: config.robots,
+        metrics: Metrics::new(routes.iter().map(|route| route.path().to_string())),
+        rate_limiter: RateLimiter::new(config.rate_limit),
+    };
+
+    match scrub_variants(&state.media_dir) {
+        Ok(0) => {}
+        Ok(n) => log::info!("stripped metadata from {n} image variants"),
+        Err(e) => {
+            log::error!("couldn't strip metadata from image variants: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    // compressing the bundle takes a while and the uncompressed files can be served meanwhile
+    let site_root = std::path::PathBuf::from(&state.leptos_options.site_root);
+    tokio::task::spawn_blocking(move || match precompress(&site_root) {
+        Ok(0) => {}
+        Ok(n) => log::info!("compressed {n} static files"),
+        Err(e) => log::warn!("couldn't compress static files, serving them uncompressed: {e}"),
+    });
+
+    let mut media = Router::new();
+    for variant in Variant::ALL {
+        let dir = state.media_dir.join(variant.name());
+        media = media.nest_service(&format!("/{}", variant.name()), ServeDir::new(dir));
+    }
+
     // build our application with a route
     let app = Router::new()
-        .route("/api/*fn_name", post(leptos_axum::handle_server_fns))
-        .leptos_routes(&leptos_options, routes, App)
+        .route("/api/*fn_name", post(server_fn_handler))
+        .route("/admin/api/*fn_name", post(server_fn_handler))
+        .route(
+            "/admin/api/upload",
+            post(upload_handler).layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE)),
+        )
+        .route("/images", get(images_handler))
+        .route("/export/images.geojson", get(geojson_handler))
+        .route("/export/images.kml", get(kml_handler))
+        .route("/export/images.gpx", get(gpx_handler))
+        .route("/feed.atom", get(atom_handler))
+        .route("/feed.rss", get(rss_handler))
+        .route("/sitemap.xml", get(sitemap_handler))
+        .route("/robots.txt", get(robots_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/metrics", get(metrics_handler))
+        .nest("/media", media.layer(middleware::from_fn_with_state(CachePolicy::Immutable, cache_headers)))
+        .leptos_routes_with_context(
+            &state,
+            routes,
+            {
+                let state = state.clone();
+                move || provide_context(state.clone())
+            },
+            App,
+        )
         .fallback(file_and_error_handler)
-        .with_state(leptos_options);
+        .layer(middleware::from_fn_with_state(state.clone(), admin_guard))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .layer(middleware::from_fn(security_headers))
+        .layer(middleware::from_fn_with_state(state.clone(), track_metrics))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::make_span::<axum::body::Body>)
+                .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state);
 
-    // run our app with hyper
-    // `axum::Server` is a re-export of `hyper::Server`
+    // run our app with hyper until SIGTERM or Ctrl+C
     log::info!("listening on http://{}", &addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    shutdown::serve(app, addr, shutdown_timeout).await;
 }
 
 #[cfg(not(feature = "ssr"))]
@@ -2,15 +2,66 @@ use std::collections::BTreeMap;
 
 use leptos::*;
 use leptos_meta::*;
+use leptos_router::use_query_map;
+use serde::{Deserialize, Serialize};
 use web_sys::MouseEvent;
-use wasm_bindgen::prelude::*;
-use serde_wasm_bindgen::{from_value, to_value};
 
 use crate::api::{Image, ImagesResource};
+use crate::cats::{cat_names, CatsResource};
 use crate::leaflet::LeafletMap;
+use crate::lightbox::Lightbox;
 
-const ACCESS_TOKEN: &str = "bob";
-const MAX_ZOOM: u8 = 22;
+/// Where map tiles come from and where maps start out, configured on the server.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MapSettings {
+    /// A Leaflet URL template, e.g. `https://tile.openstreetmap.org/{z}/{x}/{y}.png`.
+    pub tile_url: String,
+    pub attribution: String,
+    /// Filled in for `{accessToken}` in the template.
+    pub access_token: String,
+    /// Filled in for `{id}` in the template, e.g. a Mapbox style.
+    pub tile_id: String,
+    pub tile_size: u32,
+    pub zoom_offset: i8,
+    pub max_zoom: u8,
+    /// Latitude and longitude of where maps start out before there is anything to show.
+    pub center: (f64, f64),
+    pub zoom: u8,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        MapSettings {
+            tile_url: "https://tile.openstreetmap.org/{z}/{x}/{y}.png".into(),
+            attribution: r#"&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> contributors"#.into(),
+            access_token: String::new(),
+            tile_id: String::new(),
+            tile_size: 256,
+            zoom_offset: 0,
+            max_zoom: 19,
+            // Bangkok
+            center: (13.7563, 100.5018),
+            zoom: 5,
+        }
+    }
+}
+
+#[server(GetMapSettings, "/api")]
+pub async fn get_map_settings() -> Result<MapSettings, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+    Ok(state.map)
+}
+
+/// Creates a map in the element `element_id`, falling back to the default tiles if the settings
+/// can't be loaded.
+pub async fn create_map(element_id: &str) -> LeafletMap {
+    let settings = get_map_settings().await.unwrap_or_else(|e| {
+        log::error!("failed to load map settings: {e}");
+        MapSettings::default()
+    });
+    LeafletMap::new(element_id, &settings)
+}
 
 #[derive(Copy, Clone)]
 struct MapResource(Resource<(), LeafletMap>);
@@ -21,9 +72,7 @@ pub fn MapView() -> impl IntoView {
 
     let map = create_local_resource(
         || (),
-        |_| async move {
-            LeafletMap::new(ACCESS_TOKEN, MAX_ZOOM)
-        });
+        |_| create_map("cattos"));
 
     provide_context(MapResource(map));
     on_cleanup(move || {
@@ -33,27 +82,56 @@ pub fn MapView() -> impl IntoView {
     });
 
     view! {
-        <Link rel="stylesheet" href="leaflet.css"/>
-        <script src="leaflet.js"></script>
-        <script src="map.js"></script>
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
         <Places/>
         <Map/>
+        <CityLightbox/>
     }
 }
 
+/// Steps through the photos taken in the same place as the one that was opened from a popup.
+#[component]
+fn CityLightbox() -> impl IntoView {
+    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let query = use_query_map();
+
+    let same_place = Signal::derive(move || {
+        let images = images.0.get().unwrap_or_default();
+        let id = query.with(|q| q.get("photo").and_then(|id| id.parse::<usize>().ok()));
+        let Some(current) = id.and_then(|id| images.iter().find(|img| img.id == id)) else {
+            return vec![];
+        };
+
+        let place = format_location(current);
+        let mut same_place: Vec<Image> = images.iter()
+            .filter(|img| format_location(img) == place)
+            .cloned()
+            .collect();
+        same_place.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        same_place
+    });
+
+    view! { <Lightbox images=same_place/> }
+}
+
 #[component]
 fn Map() -> impl IntoView {
     let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let cats = use_context::<CatsResource>().expect("it to have been loaded in App");
     let map = use_context::<MapResource>().expect("it to have been created in MapView");
 
     create_effect(move |_| {
-        if let Some(images) = images.0.get() {
+        if let (Some(images), Some(cats)) = (images.0.get(), cats.0.get()) {
             if let Some(map) = map.0.get() {
-                map.set_view(images[0].latitude, images[0].longitude, 15);
+                if let Some(first) = images.first() {
+                    map.set_view(first.latitude, first.longitude, 15);
+                }
 
                 images.iter()
                     .for_each(|img| {
-                        map.add_marker(&img, 12);
+                        map.add_marker(img, &cat_names(&cats, &img.cat_ids), 12);
                     });
             }
         }
@@ -67,6 +145,37 @@ fn Map() -> impl IntoView {
     }
 }
 
+/// Shows `images` on a map that is zoomed to fit all of them.
+#[component]
+pub fn PhotoMap(element_id: &'static str, images: Vec<Image>) -> impl IntoView {
+    let cats = use_context::<CatsResource>().expect("it to have been loaded in App");
+
+    let map = create_local_resource(
+        || (),
+        move |_| create_map(element_id));
+
+    on_cleanup(move || {
+        if let Some(map) = map() {
+            map.remove();
+        }
+    });
+
+    create_effect(move |_| {
+        if let (Some(map), Some(cats)) = (map(), cats.0.get()) {
+            let points: Vec<(f64, f64)> = images.iter()
+                .map(|img| (img.latitude, img.longitude))
+                .collect();
+
+            map.fit_points(&points, 16);
+            images.iter().for_each(|img| map.add_marker(img, &cat_names(&cats, &img.cat_ids), 12));
+        }
+    });
+
+    view! {
+        <div id=element_id class="photo-map"></div>
+    }
+}
+
 #[component]
 fn Places() -> impl IntoView {
     let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
@@ -97,16 +206,14 @@ fn Places() -> impl IntoView {
                 map.set_view(latitude, longitude, 15);
             }
 
-            if let Some(details) = details() {
-                if let Some(details) = details {
-                    details.remove_attribute("open").expect("should remove 'open' attribute");
-                }
+            if let Some(Some(details)) = details() {
+                details.remove_attribute("open").expect("should remove 'open' attribute");
             }
         }
     };
 
     view! {
-        <details id="places" role="list" style="z-index: 1337">
+        <details id="places" role="list">
             <summary aria-haspopup="listbox" role="button">
                 "Places"
             </summary>
@@ -119,6 +226,9 @@ fn Places() -> impl IntoView {
                         })
                         .collect::<Vec<_>>()
                 }}
+                <li>
+                    <a href="/places">"All places"</a>
+                </li>
             </ul>
         </details>
     }
@@ -173,14 +283,11 @@ fn PopupTemplate() -> impl IntoView {
     }
 }
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen]
-    pub fn formatLocation(image: JsValue) -> JsValue;
-}
-
+// Same as formatLocation() in map.js, but also usable during server side rendering
 pub fn format_location(image: &Image) -> String {
-    let image = to_value(image).expect("Image struct to convert successfully");
-    let loc = formatLocation(image);
-    from_value(loc).expect("it to convert to a String successfully")
+    if image.city.is_empty() {
+        image.country.clone()
+    } else {
+        format!("{}, {}", image.city, image.country)
+    }
 }
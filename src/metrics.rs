@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::{MatchedPath, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::state::AppState;
+
+/// Upper bounds in seconds, from static files to slow SSR renders.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// The route label for requests that no route matched, i.e. static files and 404s.
+pub(crate) const UNMATCHED: &str = "unmatched";
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct MetricsData {
+    /// By method, route and status code.
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// By route.
+    latencies: BTreeMap<String, Histogram>,
+    /// By route, only for pages rendered by Leptos. Streamed pages count until the first chunk.
+    renders: BTreeMap<String, Histogram>,
+}
+
+/// Request counts and latencies since the server started, served at `/metrics`.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    /// The routes of the app, to tell server-side renders from other requests.
+    pages: Arc<HashSet<String>>,
+    data: Arc<Mutex<MetricsData>>,
+}
+
+impl Metrics {
+    pub fn new(pages: impl IntoIterator<Item = String>) -> Metrics {
+        Metrics {
+            pages: Arc::new(pages.into_iter().collect()),
+            data: Default::default(),
+        }
+    }
+
+    fn record(&self, method: &str, route: &str, status: StatusCode, seconds: f64) {
+        let mut data = self.data.lock().expect("metrics lock poisoned");
+
+        *data.requests
+            .entry((method.to_string(), route.to_string(), status.as_u16()))
+            .or_default() += 1;
+        data.latencies.entry(route.to_string()).or_default().observe(seconds);
+
+        if self.pages.contains(route) {
+            data.renders.entry(route.to_string()).or_default().observe(seconds);
+        }
+    }
+}
+
+/// Label values may contain anything but these.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Times every request, labelled with the route pattern rather than the path so photo ids don't
+/// end up in the labels.
+pub async fn track_metrics<B>(State(state): State<AppState>, request: Request<B>, next: Next<B>) -> Response {
+    let route = request.extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED.to_string());
+    let method = request.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    state.metrics.record(&method, &route, response.status(), start.elapsed().as_secs_f64());
+
+    response
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    {
+        let data = state.metrics.data.lock().expect("metrics lock poisoned");
+
+        out.push_str("# HELP coa_http_requests_total HTTP requests handled.\n");
+        out.push_str("# TYPE coa_http_requests_total counter\n");
+        for ((method, route, status), count) in &data.requests {
+            let _ = writeln!(
+                out,
+                "coa_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+                escape_label(method),
+                escape_label(route),
+            );
+        }
+
+        out.push_str("# HELP coa_http_request_duration_seconds Time to respond to HTTP requests.\n");
+        out.push_str("# TYPE coa_http_request_duration_seconds histogram\n");
+        for (route, histogram) in &data.latencies {
+            let labels = format!("route=\"{}\"", escape_label(route));
+            histogram.write(&mut out, "coa_http_request_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP coa_ssr_render_duration_seconds Time until pages rendered on the server start streaming.\n");
+        out.push_str("# TYPE coa_ssr_render_duration_seconds histogram\n");
+        for (route, histogram) in &data.renders {
+            let labels = format!("route=\"{}\"", escape_label(route));
+            histogram.write(&mut out, "coa_ssr_render_duration_seconds", &labels);
+        }
+    }
+
+    let images = state.catalog.images();
+    let published = images.iter().filter(|img| !img.needs_location).count();
+    let gauges = [
+        ("coa_catalog_images", "Photos in the catalog, published or not.", images.len()),
+        ("coa_catalog_published_images", "Photos visitors can see.", published),
+        ("coa_catalog_cats", "Named cats.", state.catalog.cats().len()),
+        ("coa_collections", "Published favorites collections.", state.collections.all().len()),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}");
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
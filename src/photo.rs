@@ -0,0 +1,101 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Cat, Image};
+use crate::error_template::{AppError, ErrorTemplate};
+use crate::map::format_location;
+use crate::places::city_href;
+use crate::search::SearchQuery;
+use crate::social::PhotoMeta;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PhotoDetails {
+    pub image: Image,
+    pub cats: Vec<Cat>,
+}
+
+#[server(GetPhoto, "/api")]
+pub async fn get_photo(id: usize) -> Result<Option<PhotoDetails>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let Some(image) = state.public_images().into_iter().find(|img| img.id == id) else {
+        return Ok(None);
+    };
+
+    let cats = state.catalog.cats()
+        .into_iter()
+        .filter(|cat| image.cat_ids.contains(&cat.id))
+        .collect();
+
+    Ok(Some(PhotoDetails { image, cats }))
+}
+
+#[derive(Params, Clone, Debug, PartialEq)]
+struct PhotoParams {
+    id: usize,
+}
+
+#[component]
+pub fn PhotoPage() -> impl IntoView {
+    let params = use_params::<PhotoParams>();
+    let id = move || params.with(|p| p.as_ref().map(|p| p.id).ok());
+
+    let photo = create_resource(id, |id| async move {
+        match id {
+            Some(id) => get_photo(id).await,
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <PhotoMeta id=Signal::derive(id)/>
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || photo.get().map(|photo| match photo {
+                Ok(Some(details)) => view! { <Photo details/> }.into_view(),
+                Ok(None) => {
+                    let mut outside_errors = Errors::default();
+                    outside_errors.insert_with_default_key(AppError::NotFound);
+                    view! { <ErrorTemplate outside_errors/> }.into_view()
+                }
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn Photo(details: PhotoDetails) -> impl IntoView {
+    let PhotoDetails { image, cats } = details;
+    let alt = format!("photo #{} showing one or more cats", image.id);
+    let location = format_location(&image);
+    let place = city_href(&image);
+    let date = image.timestamp.split('T').next().unwrap_or_default().to_string();
+
+    let tags = image.tags.into_iter()
+        .map(|tag| {
+            let query = SearchQuery { tag: Some(tag.clone()), ..Default::default() };
+            let href = format!("/search?{}", query.to_query_string());
+            view! { <A href=href class="tag">"#"{tag}</A>" " }
+        })
+        .collect_view();
+
+    let cats = cats.into_iter()
+        .map(|cat| {
+            let href = format!("/cats/by-name/{}", cat.slug);
+            view! { <A href=href>{cat.name}</A>" " }
+        })
+        .collect_view();
+
+    view! {
+        <article class="photo">
+            <a href=image.url_large><img src=image.url_medium alt=alt/></a>
+            <footer>
+                {image.caption.map(|caption| view! { <p>{caption}</p> })}
+                <p>"Photo #"{image.id}". Taken on "{date}" in "<A href=place>{location}</A>"."</p>
+                <p>{cats}</p>
+                <p>{tags}</p>
+            </footer>
+        </article>
+    }
+}
@@ -0,0 +1,183 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::admin::AdminNav;
+use crate::api::{Cat, Image};
+use crate::cats::{cat_names, list_cats};
+use crate::map::format_location;
+
+/// The parts of an image that describe what is on it, as stored in the audit log.
+#[cfg(feature = "ssr")]
+#[derive(serde::Serialize)]
+struct Description {
+    caption: Option<String>,
+    tags: Vec<String>,
+    cat_ids: Vec<usize>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<&Image> for Description {
+    fn from(image: &Image) -> Self {
+        Description {
+            caption: image.caption.clone(),
+            tags: image.tags.clone(),
+            cat_ids: image.cat_ids.clone(),
+        }
+    }
+}
+
+#[server(AllImages, "/admin/api")]
+pub async fn all_images() -> Result<Vec<Image>, ServerFnError> {
+    crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    Ok(state.catalog.images())
+}
+
+#[server(SetHideLocation, "/admin/api")]
+pub async fn set_hide_location(id: usize, hide: bool) -> Result<Image, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    let before = state.catalog.get(id)
+        .ok_or_else(|| ServerFnError::ServerError(format!("no image with id {id}")))?;
+
+    let after = state.catalog
+        .update(id, |img| img.hide_location = hide)
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    state.audit
+        .record(&user, id, "hide_location", before.hide_location, after.hide_location)
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    Ok(after)
+}
+
+/// Sets caption, tags and cats of an image. Tags and cats are comma-separated, cats are matched
+/// by name and created if they don't exist yet.
+#[server(SaveDescription, "/admin/api")]
+pub async fn save_description(
+    id: usize,
+    caption: String,
+    tags: String,
+    cats: String,
+) -> Result<Image, ServerFnError> {
+    let user = crate::auth::require_admin().await?;
+    let state = crate::state::use_app_state()?;
+
+    let before = state.catalog.get(id)
+        .ok_or_else(|| ServerFnError::ServerError(format!("no image with id {id}")))?;
+
+    let mut tags: Vec<String> = split_list(&tags).map(str::to_lowercase).collect();
+    tags.sort();
+    tags.dedup();
+
+    let cats: Vec<&str> = split_list(&cats).collect();
+    let caption = Some(caption.trim().to_string()).filter(|c| !c.is_empty());
+
+    let after = state.catalog
+        .describe(id, caption, tags, &cats)
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    state.audit
+        .record(&user, id, "describe", Description::from(&before), Description::from(&after))
+        .map_err(|e| ServerFnError::ServerError(format!("failed to write audit log: {e}")))?;
+
+    Ok(after)
+}
+
+#[cfg(feature = "ssr")]
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+#[component]
+pub fn AdminPhotos() -> impl IntoView {
+    let set_hide = create_server_action::<SetHideLocation>();
+    let describe = create_server_action::<SaveDescription>();
+    let images = create_resource(
+        move || (set_hide.version().get(), describe.version().get()),
+        |_| async move { Ok::<_, ServerFnError>((all_images().await?, list_cats().await?)) });
+
+    view! {
+        <script src="/map.js"></script>
+
+        <AdminNav/>
+        <h1>"Photos"</h1>
+        {move || set_hide.value().get().and_then(Result::err).map(|e| view! {
+            <p class="upload-error">{e.to_string()}</p>
+        })}
+        {move || describe.value().get().and_then(Result::err).map(|e| view! {
+            <p class="upload-error">{e.to_string()}</p>
+        })}
+        <Transition fallback=|| view! { <progress></progress> }>
+            {move || images.get().map(|images| match images {
+                Err(e) => view! { <p class="upload-error">{e.to_string()}</p> }.into_view(),
+                Ok((images, cats)) => view! {
+                    <table>
+                        <thead>
+                            <tr>
+                                <th>"Photo"</th>
+                                <th>"Taken"</th>
+                                <th>"Location"</th>
+                                <th>"Hide exact location"</th>
+                                <th>"Description"</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {images.into_iter()
+                                .map(|image| view! { <PhotoRow image cats=cats.clone() set_hide describe/> })
+                                .collect_view()}
+                        </tbody>
+                    </table>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+fn PhotoRow(
+    image: Image,
+    cats: Vec<Cat>,
+    set_hide: Action<SetHideLocation, Result<Image, ServerFnError>>,
+    describe: Action<SaveDescription, Result<Image, ServerFnError>>,
+) -> impl IntoView {
+    let id = image.id;
+    let alt = format!("photo #{id}");
+    let location = if image.needs_location {
+        "no location".to_string()
+    } else {
+        format!("{} ({:.5}, {:.5})", format_location(&image), image.latitude, image.longitude)
+    };
+    let tags = image.tags.join(", ");
+    let cat_names = cat_names(&cats, &image.cat_ids).join(", ");
+
+    view! {
+        <tr>
+            <td><img src=image.url_small alt=alt class="photo-thumb"/>" #"{id}</td>
+            <td>{image.timestamp}</td>
+            <td>{location}</td>
+            <td>
+                <input
+                    type="checkbox"
+                    role="switch"
+                    prop:checked=image.hide_location
+                    on:change=move |ev| set_hide.dispatch(SetHideLocation {
+                        id,
+                        hide: event_target_checked(&ev),
+                    })
+                />
+            </td>
+            <td>
+                <ActionForm action=describe>
+                    <input type="hidden" name="id" value=id/>
+                    <input name="caption" placeholder="Caption" value=image.caption/>
+                    <input name="tags" placeholder="Tags, comma-separated" value=tags/>
+                    <input name="cats" placeholder="Cats, comma-separated" value=cat_names/>
+                    <input type="submit" value="Save"/>
+                </ActionForm>
+            </td>
+        </tr>
+    }
+}
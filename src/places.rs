@@ -0,0 +1,241 @@
+use leptos::*;
+use leptos_meta::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{slugify, Image};
+use crate::error_template::{AppError, ErrorTemplate};
+use crate::gallery::Thumbnail;
+use crate::lightbox::Lightbox;
+use crate::map::PhotoMap;
+
+const MAP_ELEMENT_ID: &str = "city-map";
+
+/// Used in URLs for places geocoding couldn't name, a city or the country too.
+const UNKNOWN: &str = "unknown";
+
+/// Shown for photos geocoding couldn't find a country for.
+const UNKNOWN_COUNTRY: &str = "Somewhere in Asia";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CountrySummary {
+    pub name: String,
+    pub slug: String,
+    pub photos: usize,
+    /// Sorted by name.
+    pub cities: Vec<CitySummary>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CitySummary {
+    pub name: String,
+    pub slug: String,
+    pub photos: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CityDetails {
+    pub country: String,
+    pub city: String,
+    /// Newest first.
+    pub images: Vec<Image>,
+}
+
+/// The slug of a city or country, never empty so URLs keep all their parts.
+pub(crate) fn place_slug(name: &str) -> String {
+    match slugify(name) {
+        slug if slug.is_empty() => UNKNOWN.to_string(),
+        slug => slug,
+    }
+}
+
+pub(crate) fn country_name(country: &str) -> &str {
+    match country {
+        "" => UNKNOWN_COUNTRY,
+        country => country,
+    }
+}
+
+pub fn city_href(image: &Image) -> String {
+    format!("/places/{}/{}", place_slug(&image.country), place_slug(&image.city))
+}
+
+#[server(ListCountries, "/api")]
+pub async fn list_countries() -> Result<Vec<CountrySummary>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+    let mut countries: Vec<CountrySummary> = vec![];
+
+    for image in state.public_images() {
+        let country = match countries.iter_mut().find(|c| c.name == image.country) {
+            Some(country) => country,
+            None => {
+                countries.push(CountrySummary {
+                    name: image.country.clone(),
+                    slug: place_slug(&image.country),
+                    photos: 0,
+                    cities: vec![],
+                });
+                countries.last_mut().expect("country to have just been added")
+            }
+        };
+        country.photos += 1;
+
+        match country.cities.iter_mut().find(|c| c.name == image.city) {
+            Some(city) => city.photos += 1,
+            None => country.cities.push(CitySummary {
+                name: image.city.clone(),
+                slug: place_slug(&image.city),
+                photos: 1,
+            }),
+        }
+    }
+
+    countries.sort_by(|a, b| a.name.cmp(&b.name));
+    for country in countries.iter_mut() {
+        country.cities.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    Ok(countries)
+}
+
+#[server(GetCity, "/api")]
+pub async fn get_city(country: String, city: String) -> Result<Option<CityDetails>, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+
+    let mut images: Vec<Image> = state.public_images()
+        .into_iter()
+        .filter(|img| place_slug(&img.country) == country && place_slug(&img.city) == city)
+        .collect();
+
+    let Some(first) = images.first() else {
+        return Ok(None);
+    };
+
+    let (country, city) = (first.country.clone(), first.city.clone());
+    images.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    Ok(Some(CityDetails { country, city, images }))
+}
+
+#[component]
+pub fn Places() -> impl IntoView {
+    let countries = create_resource(|| (), |_| list_countries());
+
+    view! {
+        <Title text="Places - Cats of Asia"/>
+        <h1>"Places"</h1>
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || countries.get().map(|countries| match countries {
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                Ok(countries) => countries.into_iter()
+                    .map(|country| view! { <Country country/> })
+                    .collect_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn Country(country: CountrySummary) -> impl IntoView {
+    let cities = country.cities.into_iter()
+        .map(|city| {
+            let href = format!("/places/{}/{}", country.slug, city.slug);
+            let name = if city.name.is_empty() { "Elsewhere".to_string() } else { city.name };
+            view! {
+                <li><A href=href>{name}</A>" ("{city.photos}")"</li>
+            }
+        })
+        .collect_view();
+
+    let feed = format!("/feed.atom?country={}", country.slug);
+    let name = country_name(&country.name).to_string();
+    let follow = format!("Follow new cats from {name}");
+
+    view! {
+        <section>
+            <h2>{name}" "<small>{country.photos}" photos"</small></h2>
+            <ul>{cities}</ul>
+            <a href=feed>{follow}</a>
+        </section>
+    }
+}
+
+#[derive(Params, Clone, Debug, PartialEq)]
+struct CityParams {
+    country: String,
+    city: String,
+}
+
+#[component]
+pub fn City() -> impl IntoView {
+    let params = use_params::<CityParams>();
+    let place = move || params.with(|p| p.as_ref().map(|p| (p.country.clone(), p.city.clone())).ok());
+
+    let details = create_resource(place, |place| async move {
+        match place {
+            Some((country, city)) => get_city(country, city).await,
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <Link rel="stylesheet" href="/leaflet.css"/>
+        <script src="/leaflet.js"></script>
+        <script src="/map.js"></script>
+
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || details.get().map(|details| match details {
+                Ok(Some(details)) => view! { <CityView details/> }.into_view(),
+                Ok(None) => {
+                    let mut outside_errors = Errors::default();
+                    outside_errors.insert_with_default_key(AppError::NotFound);
+                    view! { <ErrorTemplate outside_errors/> }.into_view()
+                }
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn CityView(details: CityDetails) -> impl IntoView {
+    let CityDetails { country, city, images } = details;
+    let country = country_name(&country).to_string();
+    let title = if city.is_empty() { country.clone() } else { format!("{city}, {country}") };
+    let photos = images.clone().into_iter()
+        .map(|image| view! { <Thumbnail image/> })
+        .collect_view();
+    let lightbox_images = images.clone();
+
+    view! {
+        <Title text=format!("Cats of {title} - Cats of Asia")/>
+        <nav aria-label="breadcrumb">
+            <ul>
+                <li><A href="/places">"Places"</A></li>
+                <li>{country}</li>
+            </ul>
+        </nav>
+        <hgroup>
+            <h1>{title}</h1>
+            <p>{images.len()}" photos"</p>
+        </hgroup>
+        <PhotoMap element_id=MAP_ELEMENT_ID images/>
+        <Lightbox images=Signal::derive(move || lightbox_images.clone())/>
+        <div class="gallery">{photos}</div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_places_geocoding_couldnt_name() {
+        let image = |city: &str, country: &str| Image { city: city.into(), country: country.into(), ..Default::default() };
+
+        assert_eq!(city_href(&image("Chiang Mai", "Thailand")), "/places/thailand/chiang-mai");
+        assert_eq!(city_href(&image("", "Thailand")), "/places/thailand/unknown");
+        assert_eq!(city_href(&image("", "")), "/places/unknown/unknown");
+        assert_eq!(country_name(""), UNKNOWN_COUNTRY);
+    }
+}
@@ -0,0 +1,256 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::api::Image;
+use crate::ingest::Variant;
+
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// How precisely the location of a photo is published.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum LocationPrecision {
+    Exact,
+    /// Round coordinates to multiples of `grid` degrees.
+    Snap { grid: f64 },
+    /// Move coordinates up to `radius` meters in a direction derived from the photo's hash and
+    /// [`PrivacySettings::secret`], so the same photo always ends up in the same spot and averaging
+    /// requests doesn't help.
+    Fuzz { radius: f64 },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PrivacySettings {
+    pub precision: LocationPrecision,
+    /// The grid in degrees that photos flagged with `hide_location` are snapped to.
+    pub hidden_grid: f64,
+    /// Keys the direction and distance coordinates are fuzzed by. The photo's hash is part of
+    /// every image URL, so without a secret anyone could undo the fuzzing.
+    pub secret: String,
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        PrivacySettings {
+            // roughly 100m
+            precision: LocationPrecision::Snap { grid: 0.001 },
+            // roughly 5km
+            hidden_grid: 0.05,
+            secret: String::new(),
+        }
+    }
+}
+
+impl PrivacySettings {
+    /// Turns a catalog entry into what is shown to visitors.
+    pub fn publish(&self, mut image: Image) -> Image {
+        let precision = if image.hide_location {
+            match self.precision {
+                LocationPrecision::Snap { grid } if grid > self.hidden_grid => self.precision,
+                _ => LocationPrecision::Snap { grid: self.hidden_grid },
+            }
+        } else {
+            self.precision
+        };
+
+        let (latitude, longitude) = match precision {
+            LocationPrecision::Exact => (image.latitude, image.longitude),
+            LocationPrecision::Snap { grid } => (snap(image.latitude, grid), snap(image.longitude, grid)),
+            LocationPrecision::Fuzz { radius } => fuzz(&image, radius, &self.secret),
+        };
+
+        image.latitude = latitude;
+        image.longitude = longitude;
+        image
+    }
+}
+
+fn snap(coordinate: f64, grid: f64) -> f64 {
+    if grid <= 0.0 {
+        return coordinate;
+    }
+    (coordinate / grid).round() * grid
+}
+
+fn fuzz(image: &Image, radius: f64, secret: &str) -> (f64, f64) {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(image.sha256.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let seed = u64::from_be_bytes(digest[..8].try_into().expect("a SHA-256 digest has 32 bytes"));
+    let angle = (seed >> 32) as f64 / u32::MAX as f64 * 2.0 * PI;
+    // the square root spreads the points evenly over the circle instead of bunching them up in
+    // the middle
+    let distance = ((seed & 0xffff_ffff) as f64 / u32::MAX as f64).sqrt() * radius;
+
+    let latitude = image.latitude + distance * angle.cos() / METERS_PER_DEGREE;
+    let longitude = image.longitude
+        + distance * angle.sin() / (METERS_PER_DEGREE * image.latitude.to_radians().cos().max(0.01));
+
+    (latitude, longitude)
+}
+
+/// Removes EXIF and XMP segments from a JPEG. Returns `None` if `data` isn't a JPEG or has no such
+/// segments.
+pub fn strip_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    const SOI: [u8; 2] = [0xff, 0xd8];
+    const APP1: u8 = 0xe1;
+    const SOS: u8 = 0xda;
+
+    if !data.starts_with(&SOI) {
+        return None;
+    }
+
+    let mut out = SOI.to_vec();
+    let mut pos = 2;
+    let mut stripped = false;
+
+    while pos + 4 <= data.len() && data[pos] == 0xff {
+        let marker = data[pos + 1];
+        if marker == SOS {
+            break;
+        }
+
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let end = (pos + 2 + length).min(data.len());
+
+        if marker == APP1 {
+            stripped = true;
+        } else {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+
+    if !stripped {
+        return None;
+    }
+
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
+
+/// Strips metadata from every variant in `media_dir`, for files that were not generated by the
+/// ingest pipeline, which never writes any. Returns the number of files that were changed.
+pub fn scrub_variants(media_dir: &Path) -> io::Result<usize> {
+    let mut scrubbed = 0;
+
+    for variant in Variant::ALL {
+        let dir = media_dir.join(variant.name());
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if let Some(data) = strip_metadata(&fs::read(&path)?) {
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, data)?;
+                fs::rename(&tmp, &path)?;
+                scrubbed += 1;
+            }
+        }
+    }
+
+    Ok(scrubbed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    fn image(latitude: f64, longitude: f64) -> Image {
+        Image {
+            sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into(),
+            latitude,
+            longitude,
+            ..Default::default()
+        }
+    }
+
+    /// Distance in meters, good enough for the small offsets of fuzzing.
+    fn meters(a: &Image, b: &Image) -> f64 {
+        let north = (a.latitude - b.latitude) * METERS_PER_DEGREE;
+        let east = (a.longitude - b.longitude) * METERS_PER_DEGREE * a.latitude.to_radians().cos();
+        north.hypot(east)
+    }
+
+    fn fuzzing(radius: f64, secret: &str) -> PrivacySettings {
+        PrivacySettings {
+            precision: LocationPrecision::Fuzz { radius },
+            secret: secret.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn snaps_to_grid() {
+        assert!((snap(13.75634, 0.001) - 13.756).abs() < 1e-9);
+        assert!((snap(100.50186, 0.001) - 100.502).abs() < 1e-9);
+        assert!((snap(-33.86, 0.5) - -34.0).abs() < 1e-9);
+        assert_eq!(snap(13.75634, 0.0), 13.75634);
+    }
+
+    #[test]
+    fn fuzzes_within_radius() {
+        let original = image(13.7563, 100.5018);
+        let settings = fuzzing(200.0, SECRET);
+        let fuzzed = settings.publish(original.clone());
+
+        assert!(meters(&original, &fuzzed) <= 200.0);
+        assert_ne!((fuzzed.latitude, fuzzed.longitude), (original.latitude, original.longitude));
+        // always the same spot, so averaging doesn't help
+        assert_eq!(settings.publish(original.clone()), fuzzed);
+    }
+
+    #[test]
+    fn fuzzes_depending_on_secret() {
+        let original = image(13.7563, 100.5018);
+        let fuzzed = fuzzing(200.0, SECRET).publish(original.clone());
+        let other = fuzzing(200.0, "fedcba9876543210").publish(original);
+
+        assert_ne!((fuzzed.latitude, fuzzed.longitude), (other.latitude, other.longitude));
+    }
+
+    #[test]
+    fn hidden_locations_use_the_coarser_grid() {
+        let mut hidden = image(13.7563, 100.5018);
+        hidden.hide_location = true;
+
+        let published = PrivacySettings::default().publish(hidden.clone());
+        assert!((published.latitude - 13.75).abs() < 1e-9);
+        assert!((published.longitude - 100.5).abs() < 1e-9);
+
+        // fuzzing isn't enough either
+        let published = fuzzing(200.0, SECRET).publish(hidden);
+        assert!((published.latitude - 13.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exact_keeps_coordinates() {
+        let settings = PrivacySettings { precision: LocationPrecision::Exact, ..Default::default() };
+        assert_eq!(settings.publish(image(13.7563, 100.5018)), image(13.7563, 100.5018));
+    }
+
+    #[test]
+    fn strips_app1_segments() {
+        let app0 = [0xff, 0xe0, 0x00, 0x04, 0x4a, 0x46];
+        let app1 = [0xff, 0xe1, 0x00, 0x04, 0x45, 0x78];
+        let scan = [0xff, 0xda, 0x00, 0x02, 0x01, 0x02, 0xff, 0xd9];
+        let jpeg = [&[0xff, 0xd8][..], &app0, &app1, &scan].concat();
+
+        assert_eq!(strip_metadata(&jpeg), Some([&[0xff, 0xd8][..], &app0, &scan].concat()));
+        assert_eq!(strip_metadata(&[&[0xff, 0xd8][..], &app0, &scan].concat()), None);
+        assert_eq!(strip_metadata(b"GIF89a"), None);
+    }
+}
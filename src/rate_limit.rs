@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use axum::extract::{ConnectInfo, MatchedPath, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::state::AppState;
+
+/// Buckets the in-memory store keeps before it forgets the least recently used one.
+const MAX_BUCKETS: usize = 10_000;
+
+/// A token bucket: `burst` requests at once, refilled with `per_minute` requests a minute.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl RateLimit {
+    fn tokens_per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// How many requests a client may send, per group of routes. Clients are told apart by their
+/// address, or by their username once they logged into the admin area.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    /// Server functions, like search and publishing favorites.
+    pub api: RateLimit,
+    /// Photo uploads, each request being a batch.
+    pub upload: RateLimit,
+    /// The image list and the exports, which are large.
+    pub images: RateLimit,
+    /// Failed logins into the admin area, by address whatever the route.
+    pub admin_login: RateLimit,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        RateLimitSettings {
+            enabled: true,
+            api: RateLimit { burst: 60, per_minute: 300 },
+            upload: RateLimit { burst: 10, per_minute: 30 },
+            images: RateLimit { burst: 30, per_minute: 60 },
+            admin_login: RateLimit { burst: 10, per_minute: 2 },
+        }
+    }
+}
+
+impl RateLimitSettings {
+    /// The group of `route`, a route pattern like `/api/*fn_name`, and its limit.
+    fn limit(&self, route: &str) -> Option<(&'static str, RateLimit)> {
+        match route {
+            "/admin/api/upload" => Some(("upload", self.upload)),
+            // server functions can be called through either prefix
+            "/api/*fn_name" | "/admin/api/*fn_name" => Some(("api", self.api)),
+            "/images" => Some(("images", self.images)),
+            _ if route.starts_with("/export/") => Some(("images", self.images)),
+            _ => None,
+        }
+    }
+}
+
+/// Where the token buckets are kept. [`MemoryStore`] is enough for a single server, several of
+/// them would share one in e.g. Redis.
+#[async_trait]
+pub trait RateLimitStore: Debug + Send + Sync {
+    /// Takes a token from the bucket `key`, which starts out full. Fails with the time until the
+    /// next token is available if the bucket is empty.
+    async fn take(&self, key: &str, limit: RateLimit) -> Result<(), Duration>;
+
+    /// Fails like [`take`](Self::take) if the bucket `key` is empty, but leaves its tokens alone.
+    async fn peek(&self, key: &str, limit: RateLimit) -> Result<(), Duration>;
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    limit: RateLimit,
+    /// When the bucket was last taken from, counted in takes.
+    used: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.tokens_per_second()).min(f64::from(self.limit.burst));
+        self.updated = now;
+    }
+
+    /// Tells how long it takes until there is a token, if there is none.
+    fn peek(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+
+        if self.tokens >= 1.0 {
+            Ok(())
+        } else {
+            let seconds = (1.0 - self.tokens) / self.limit.tokens_per_second();
+            Err(Duration::from_secs_f64(seconds))
+        }
+    }
+
+    /// Takes a token, or tells how long it takes until there is one.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.peek(now)?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// The buckets by key and by when they were last used, to find the least recently used one.
+#[derive(Debug, Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    by_use: BTreeMap<u64, String>,
+    takes: u64,
+}
+
+/// Keeps the buckets in this process, they are lost on restart.
+#[derive(Debug)]
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+    capacity: usize,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        MemoryStore::with_capacity(MAX_BUCKETS)
+    }
+}
+
+impl MemoryStore {
+    /// A store that forgets the least recently used bucket when it has `capacity` of them. That
+    /// client starts over with a full bucket.
+    pub fn with_capacity(capacity: usize) -> MemoryStore {
+        MemoryStore { buckets: Mutex::default(), capacity }
+    }
+
+    fn take_at(&self, key: &str, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        self.with_bucket(key, limit, now, |bucket| bucket.take(now))
+    }
+
+    fn peek_at(&self, key: &str, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        self.with_bucket(key, limit, now, |bucket| bucket.peek(now))
+    }
+
+    fn with_bucket<T>(&self, key: &str, limit: RateLimit, now: Instant, f: impl FnOnce(&mut Bucket) -> T) -> T {
+        let mut buckets = self.buckets.lock().expect("rate limit lock poisoned");
+        let Buckets { by_key, by_use, takes } = &mut *buckets;
+        *takes += 1;
+
+        if let Some(bucket) = by_key.get_mut(key) {
+            by_use.remove(&bucket.used);
+            bucket.used = *takes;
+        } else {
+            if by_key.len() >= self.capacity {
+                if let Some((_, oldest)) = by_use.pop_first() {
+                    by_key.remove(&oldest);
+                }
+            }
+            let tokens = f64::from(limit.burst);
+            by_key.insert(key.to_string(), Bucket { tokens, updated: now, limit, used: *takes });
+        }
+        by_use.insert(*takes, key.to_string());
+
+        let bucket = by_key.get_mut(key).expect("bucket was just inserted");
+        // the limit may have changed with the configuration
+        bucket.limit = limit;
+        f(bucket)
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn take(&self, key: &str, limit: RateLimit) -> Result<(), Duration> {
+        self.take_at(key, limit, Instant::now())
+    }
+
+    async fn peek(&self, key: &str, limit: RateLimit) -> Result<(), Duration> {
+        self.peek_at(key, limit, Instant::now())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> RateLimiter {
+        RateLimiter::with_store(settings, MemoryStore::default())
+    }
+
+    pub fn with_store(settings: RateLimitSettings, store: impl RateLimitStore + 'static) -> RateLimiter {
+        RateLimiter { settings, store: Arc::new(store) }
+    }
+
+    /// Fails with the time to wait if `address` failed to log into the admin area too often.
+    /// Checked before the credentials, so guessing stops even for the right ones.
+    pub async fn check_login(&self, address: &str) -> Result<(), Duration> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+        self.store.peek(&format!("admin_login:{address}"), self.settings.admin_login).await
+    }
+
+    /// Counts a failed login into the admin area from `address`.
+    pub async fn failed_login(&self, address: &str) {
+        if !self.settings.enabled {
+            return;
+        }
+        if self.store.take(&format!("admin_login:{address}"), self.settings.admin_login).await.is_err() {
+            tracing::warn!(address, "too many failed admin logins");
+        }
+    }
+
+    /// Who sent `request`: the admin's username if the credentials are right, so admins don't
+    /// share their budget with visitors on the same network, otherwise the address.
+    fn client<B>(&self, state: &AppState, request: &Request<B>) -> String {
+        if let Some(admin) = &state.admin {
+            if admin.authorize(request.headers()) {
+                return format!("user:{}", admin.username());
+            }
+        }
+
+        self.address(state, request)
+    }
+
+    /// Where `request` came from. Behind a trusted proxy that is the last `X-Forwarded-For`
+    /// entry, i.e. the one the proxy added.
+    pub(crate) fn address<B>(&self, state: &AppState, request: &Request<B>) -> String {
+        let forwarded = state.trust_proxy
+            .then(|| request.headers().get("x-forwarded-for"))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|address| address.trim().parse::<IpAddr>().ok());
+        let address = forwarded.or_else(|| {
+            request.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip())
+        });
+
+        address.map(client_address).unwrap_or_else(|| "ip:".to_string())
+    }
+}
+
+/// The key of a client at `ip`. IPv6 hosts usually get a whole /64 to pick addresses from, so
+/// they are told apart by that prefix instead of by the address they happen to use.
+fn client_address(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => format!("ip:{ip}"),
+        IpAddr::V6(ip) => {
+            let prefix = Ipv6Addr::from(u128::from(ip) & !(u128::MAX >> 64));
+            format!("ip:{prefix}/64")
+        }
+    }
+}
+
+/// `Retry-After` takes whole seconds, rounded up so clients don't come back too early.
+fn retry_after_seconds(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// Answers with a 429 to clients that used up the requests of the route's group.
+pub async fn rate_limit<B>(State(state): State<AppState>, request: Request<B>, next: Next<B>) -> Response {
+    let limiter = &state.rate_limiter;
+    if !limiter.settings.enabled {
+        return next.run(request).await;
+    }
+
+    let Some((group, limit)) = request.extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| limiter.settings.limit(route.as_str()))
+    else {
+        return next.run(request).await;
+    };
+
+    let client = limiter.client(&state, &request);
+    match limiter.store.take(&format!("{group}:{client}"), limit).await {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            tracing::warn!(group, client, "rate limited");
+            too_many_requests(retry_after)
+        }
+    }
+}
+
+pub(crate) fn too_many_requests(retry_after: Duration) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_seconds(retry_after).to_string())],
+        "too many requests, try again later",
+    )
+        .into_response()
+}
+
+/// The address a server function request came from, provided as context by the server function
+/// handler, for [`RateLimiter::check_login`].
+#[derive(Clone, Debug)]
+pub(crate) struct ClientAddress(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit { burst: 2, per_minute: 60 };
+
+    #[test]
+    fn refills_one_token_at_a_time_up_to_the_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 0.0, updated: start, limit: LIMIT, used: 0 };
+
+        bucket.refill(start + Duration::from_millis(500));
+        assert_eq!(bucket.tokens, 0.5);
+        bucket.refill(start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn tells_how_long_until_the_next_token() {
+        let store = MemoryStore::default();
+        let start = Instant::now();
+
+        assert_eq!(store.take_at("client", LIMIT, start), Ok(()));
+        assert_eq!(store.take_at("client", LIMIT, start), Ok(()));
+        assert_eq!(store.take_at("client", LIMIT, start), Err(Duration::from_secs(1)));
+        // other clients have their own bucket
+        assert_eq!(store.take_at("other", LIMIT, start), Ok(()));
+
+        let later = start + Duration::from_millis(750);
+        let wait = store.take_at("client", LIMIT, later).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.25).abs() < 1e-9);
+        assert_eq!(retry_after_seconds(wait), 1);
+        assert_eq!(store.take_at("client", LIMIT, start + Duration::from_secs(1)), Ok(()));
+    }
+
+    #[test]
+    fn peeks_without_taking() {
+        let store = MemoryStore::default();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(store.peek_at("client", LIMIT, now), Ok(()));
+        }
+        let _ = store.take_at("client", LIMIT, now);
+        let _ = store.take_at("client", LIMIT, now);
+        assert_eq!(store.peek_at("client", LIMIT, now), Err(Duration::from_secs(1)));
+        assert_eq!(store.peek_at("client", LIMIT, now + Duration::from_secs(1)), Ok(()));
+    }
+
+    #[test]
+    fn keys_ipv6_clients_by_their_prefix() {
+        let key = |ip: &str| client_address(ip.parse().unwrap());
+
+        assert_eq!(key("203.0.113.7"), "ip:203.0.113.7");
+        assert_eq!(key("2001:db8:1:2:aaaa::1"), "ip:2001:db8:1:2::/64");
+        assert_eq!(key("2001:db8:1:2:bbbb::2"), key("2001:db8:1:2:aaaa::1"));
+        assert_ne!(key("2001:db8:1:3::1"), key("2001:db8:1:2::1"));
+        // IPv4 clients of a dual stack listener
+        assert_eq!(key("::ffff:203.0.113.7"), "ip:203.0.113.7");
+    }
+
+    #[test]
+    fn rounds_retry_after_up() {
+        assert_eq!(retry_after_seconds(Duration::ZERO), 1);
+        assert_eq!(retry_after_seconds(Duration::from_millis(1001)), 2);
+        assert_eq!(retry_after_seconds(Duration::from_secs(30)), 30);
+    }
+
+    #[test]
+    fn forgets_the_least_recently_used_bucket() {
+        let store = MemoryStore::with_capacity(2);
+        let now = Instant::now();
+
+        for key in ["a", "a", "b", "a"] {
+            let _ = store.take_at(key, LIMIT, now);
+        }
+        // "b" was used longer ago than "a"
+        let _ = store.take_at("c", LIMIT, now);
+        assert_eq!(store.take_at("a", LIMIT, now), Err(Duration::from_secs(1)));
+        // "b" starts over with a full bucket
+        assert_eq!(store.take_at("b", LIMIT, now), Ok(()));
+        assert_eq!(store.buckets.lock().unwrap().by_key.len(), 2);
+    }
+}
@@ -0,0 +1,278 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Cat, Image, ImagesResource};
+use crate::cats::CatsResource;
+use crate::lightbox::{use_lightbox_href, Lightbox};
+
+/// The filters of the search page. They are passed as query parameters, so a search can be
+/// linked to, and every field that is set has to match.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SearchQuery {
+    /// Free text, matched against caption, tags, cat names and location.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub q: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub tag: Option<String>,
+    /// The slug of a cat.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub cat: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub city: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn from_query_map(query: &ParamsMap) -> SearchQuery {
+        let get = |key| query.get(key).filter(|v| !v.is_empty()).cloned();
+
+        SearchQuery {
+            q: get("q"),
+            tag: get("tag"),
+            cat: get("cat"),
+            country: get("country"),
+            city: get("city"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == SearchQuery::default()
+    }
+
+    pub fn to_query_string(&self) -> String {
+        [("q", &self.q), ("tag", &self.tag), ("cat", &self.cat), ("country", &self.country), ("city", &self.city)]
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value.as_ref().map(|v| format!("{key}={}", encode_query_value(v)))
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    pub fn matches(&self, image: &Image, cats: &[Cat]) -> bool {
+        let image_cats: Vec<&Cat> = cats.iter().filter(|cat| image.cat_ids.contains(&cat.id)).collect();
+
+        let tag_ok = self.tag.as_ref()
+            .is_none_or(|tag| image.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)));
+
+        let cat_ok = self.cat.as_ref()
+            .is_none_or(|slug| image_cats.iter().any(|cat| &cat.slug == slug));
+
+        let country_ok = self.country.as_ref()
+            .is_none_or(|country| image.country.eq_ignore_ascii_case(country));
+
+        let city_ok = self.city.as_ref()
+            .is_none_or(|city| image.city.eq_ignore_ascii_case(city));
+
+        let text_ok = self.q.as_ref().is_none_or(|q| {
+            let q = q.to_lowercase();
+            let contains = |s: &str| s.to_lowercase().contains(&q);
+
+            image.caption.as_deref().is_some_and(contains)
+                || image.tags.iter().any(|t| contains(t))
+                || image_cats.iter().any(|cat| contains(&cat.name))
+                || contains(&image.city)
+                || contains(&image.country)
+        });
+
+        tag_ok && cat_ok && country_ok && city_ok && text_ok
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, so this works the same in the browser
+/// and on the server.
+fn encode_query_value(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[component]
+pub fn Search() -> impl IntoView {
+    let images = use_context::<ImagesResource>().expect("it to have been loaded in App");
+    let cats = use_context::<CatsResource>().expect("it to have been loaded in App");
+    let query_map = use_query_map();
+
+    let query = move || SearchQuery::from_query_map(&query_map.get());
+
+    let results = Signal::derive(move || {
+        let query = query();
+        let cats = cats.0.get().unwrap_or_default();
+
+        images.0.get()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|img| query.matches(img, &cats))
+            .collect::<Vec<_>>()
+    });
+
+    view! {
+        <Form method="GET" action="">
+            <input
+                type="search"
+                name="q"
+                placeholder="Search for cats, tags or places"
+                prop:value=move || query().q.unwrap_or_default()
+            />
+        </Form>
+        <ActiveFilters query=Signal::derive(query)/>
+        <DownloadLinks query=Signal::derive(move || query().to_query_string())/>
+
+        <Lightbox images=results/>
+        <div class="search-results">
+            {move || {
+                let results = results.get();
+                if results.is_empty() {
+                    view! { <p>"No cats found."</p> }.into_view()
+                } else {
+                    results.into_iter()
+                        .map(|image| view! { <SearchResult image/> })
+                        .collect_view()
+                }
+            }}
+        </div>
+    }
+}
+
+/// Links to the exports of the images selected by `query`.
+#[component]
+pub fn DownloadLinks(#[prop(into)] query: Signal<String>) -> impl IntoView {
+    let href = move |format: &str| format!("/export/images.{format}?{}", query.get());
+
+    view! {
+        <p>
+            "Download as "
+            <a href=move || href("geojson") download>"GeoJSON"</a>", "
+            <a href=move || href("kml") download>"KML"</a>" or "
+            <a href=move || href("gpx") download>"GPX"</a>
+        </p>
+    }
+}
+
+#[component]
+fn ActiveFilters(query: Signal<SearchQuery>) -> impl IntoView {
+    let cats = use_context::<CatsResource>().expect("it to have been loaded in App");
+
+    let filters = move || {
+        let query = query.get();
+        let cat_name = query.cat.as_ref().map(|slug| {
+            cats.0.get()
+                .unwrap_or_default()
+                .into_iter()
+                .find(|cat| &cat.slug == slug)
+                .map(|cat| cat.name)
+                .unwrap_or_else(|| slug.clone())
+        });
+
+        [
+            query.tag.map(|tag| format!("tag: {tag}")),
+            cat_name.map(|name| format!("cat: {name}")),
+            query.city.map(|city| format!("city: {city}")),
+            query.country.map(|country| format!("country: {country}")),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|filter| view! { <mark>{filter}</mark>" " })
+        .collect_view()
+    };
+
+    view! {
+        <p>
+            {filters}
+            <Show when=move || !query.get().is_empty() fallback=|| ()>
+                <A href="/search">"clear"</A>
+            </Show>
+        </p>
+    }
+}
+
+#[component]
+fn SearchResult(image: Image) -> impl IntoView {
+    let href = use_lightbox_href()(image.id);
+    let alt = format!("photo #{} showing one or more cats", image.id);
+
+    view! {
+        <div class="fav-card">
+            <article>
+                <A href=href><img src=image.url_small alt=alt loading="lazy"/></A>
+                {image.caption.map(|caption| view! { <p>{caption}</p> })}
+            </article>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cat(id: usize, name: &str) -> Cat {
+        Cat { id, name: name.into(), slug: Cat::slugify(name), description: None }
+    }
+
+    fn image() -> Image {
+        Image {
+            city: "Chiang Mai".into(),
+            country: "Thailand".into(),
+            tags: vec!["temple".into(), "sleeping".into()],
+            caption: Some("Napping by the gate".into()),
+            cat_ids: vec![2],
+            ..Default::default()
+        }
+    }
+
+    fn query(pairs: &[(&str, &str)]) -> SearchQuery {
+        let mut map = ParamsMap::new();
+        for (key, value) in pairs {
+            map.insert(key.to_string(), value.to_string());
+        }
+        SearchQuery::from_query_map(&map)
+    }
+
+    #[test]
+    fn reads_query_parameters() {
+        let query = query(&[("q", "gate"), ("tag", "temple"), ("city", ""), ("other", "x")]);
+
+        assert_eq!(query.q.as_deref(), Some("gate"));
+        assert_eq!(query.tag.as_deref(), Some("temple"));
+        // empty fields of the form don't filter anything
+        assert_eq!(query.city, None);
+        assert!(self::query(&[("city", "")]).is_empty());
+    }
+
+    #[test]
+    fn writes_query_parameters() {
+        let query = query(&[("q", "mr whiskers & co"), ("country", "Thailand")]);
+        assert_eq!(query.to_query_string(), "q=mr%20whiskers%20%26%20co&country=Thailand");
+    }
+
+    #[test]
+    fn matches_every_field() {
+        let cats = [cat(1, "Tom"), cat(2, "Mr. Whiskers")];
+        let image = image();
+
+        assert!(query(&[]).matches(&image, &cats));
+        assert!(query(&[("tag", "Temple")]).matches(&image, &cats));
+        assert!(query(&[("cat", "mr-whiskers")]).matches(&image, &cats));
+        assert!(query(&[("country", "thailand"), ("city", "chiang mai")]).matches(&image, &cats));
+
+        assert!(!query(&[("tag", "kitten")]).matches(&image, &cats));
+        assert!(!query(&[("cat", "tom")]).matches(&image, &cats));
+        assert!(!query(&[("tag", "temple"), ("city", "Bangkok")]).matches(&image, &cats));
+    }
+
+    #[test]
+    fn matches_text_anywhere() {
+        let cats = [cat(2, "Mr. Whiskers")];
+        let image = image();
+
+        for text in ["NAPPING", "sleep", "whiskers", "chiang", "thai"] {
+            assert!(query(&[("q", text)]).matches(&image, &cats), "{text}");
+        }
+        assert!(!query(&[("q", "kitten")]).matches(&image, &cats));
+    }
+}
@@ -0,0 +1,95 @@
+use axum::http::{header, HeaderName, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use leptos::nonce::use_nonce;
+use leptos::use_context;
+use leptos_axum::ResponseOptions;
+
+use crate::map::MapSettings;
+use crate::state::AppState;
+
+/// Sent with everything. Browsers ignore HSTS on plain HTTP, so it doesn't get in the way locally.
+const HEADERS: [(HeaderName, &str); 5] = [
+    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    // tile providers want to know which site their tiles are shown on
+    (header::REFERRER_POLICY, "strict-origin-when-cross-origin"),
+    (header::STRICT_TRANSPORT_SECURITY, "max-age=31536000"),
+    (header::X_FRAME_OPTIONS, "DENY"),
+    (
+        HeaderName::from_static("permissions-policy"),
+        "camera=(), microphone=(), geolocation=(), payment=(), usb=()",
+    ),
+];
+
+/// For everything that isn't a page, like server function responses, feeds and images.
+const DEFAULT_POLICY: &str = "default-src 'none'; frame-ancestors 'none'";
+
+/// The scheme and host of a Leaflet URL template, with `{s}` subdomains as a wildcard.
+fn origin(url_template: &str) -> String {
+    let Some((scheme, rest)) = url_template.split_once("://") else {
+        return String::new();
+    };
+    let host = rest.split('/').next().unwrap_or_default().replace("{s}", "*");
+
+    match host.contains('{') {
+        // any other placeholder could be anything
+        true => format!("{scheme}:"),
+        false => format!("{scheme}://{host}"),
+    }
+}
+
+/// The policy for pages, with the nonce Leptos puts on the inline scripts of this render. Other
+/// scripts come from the site itself, as does everything else but the map tiles.
+fn page_policy(nonce: &str, map: &MapSettings, reload_port: Option<u32>) -> String {
+    let mut connect = "'self'".to_string();
+    if let Some(port) = reload_port {
+        // `cargo leptos watch` reloads the page through a websocket
+        connect.push_str(&format!(" ws://*:{port}"));
+    }
+
+    format!(
+        "default-src 'self'; \
+         script-src 'self' 'nonce-{nonce}' 'wasm-unsafe-eval'; \
+         style-src 'self' 'nonce-{nonce}'; \
+         img-src 'self' data: {tiles}; \
+         connect-src {connect}; \
+         object-src 'none'; \
+         base-uri 'none'; \
+         form-action 'self'; \
+         frame-ancestors 'none'",
+        tiles = origin(&map.tile_url),
+    )
+}
+
+/// Sets the policy for the page being rendered, as only the render knows its nonce.
+pub fn provide_page_policy() {
+    let (Some(nonce), Some(state), Some(response)) =
+        (use_nonce(), use_context::<AppState>(), use_context::<ResponseOptions>())
+    else {
+        return;
+    };
+
+    // the same condition Leptos uses to add the reload script
+    let reload_port = std::env::var("LEPTOS_WATCH").is_ok().then_some(state.leptos_options.reload_port);
+    let policy = page_policy(&nonce, &state.map, reload_port);
+
+    if let Ok(policy) = HeaderValue::from_str(&policy) {
+        response.insert_header(header::CONTENT_SECURITY_POLICY, policy);
+    }
+}
+
+/// Adds the security headers to every response, and a policy that allows nothing to those that
+/// don't have one of their own.
+pub async fn security_headers<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    for (name, value) in HEADERS {
+        headers.insert(name, HeaderValue::from_static(value));
+    }
+    headers
+        .entry(header::CONTENT_SECURITY_POLICY)
+        .or_insert(HeaderValue::from_static(DEFAULT_POLICY));
+
+    response
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+
+use crate::api::Image;
+use crate::export::{base_url, escape_xml};
+use crate::places::city_href;
+use crate::state::AppState;
+
+/// Paths crawlers are asked to stay out of, on top of the configured ones.
+const DISALLOWED: [&str; 4] = ["/admin/", "/api/", "/export/", "/images"];
+
+/// Pages that always exist, they change whenever a photo is added.
+const PAGES: [&str; 5] = ["/", "/gallery", "/places", "/stats", "/search"];
+
+/// What `robots.txt` tells crawlers.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RobotsSettings {
+    /// Asks crawlers to stay away entirely, e.g. for a staging site.
+    pub disallow_all: bool,
+    pub disallow: Vec<String>,
+}
+
+pub async fn robots_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let settings = &state.robots;
+    let mut robots = String::from("User-agent: *\n");
+
+    if settings.disallow_all {
+        robots.push_str("Disallow: /\n");
+    } else {
+        for path in DISALLOWED.iter().copied().chain(settings.disallow.iter().map(String::as_str)) {
+            let _ = writeln!(robots, "Disallow: {path}");
+        }
+        let _ = writeln!(robots, "\nSitemap: {}/sitemap.xml", base_url(&state, &headers));
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], robots)
+}
+
+/// The date part of a timestamp, which is all a sitemap needs.
+fn day(timestamp: &str) -> &str {
+    timestamp.split('T').next().unwrap_or_default()
+}
+
+/// Lists every page with its own URL: photos, places, cats and collections.
+pub async fn sitemap_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let images = state.public_images();
+    let base_url = base_url(&state, &headers);
+
+    // path to the newest timestamp on it
+    let mut pages: BTreeMap<String, String> = BTreeMap::new();
+    let mut add = |path: String, timestamp: &str| {
+        let lastmod = pages.entry(path).or_default();
+        if lastmod.as_str() < timestamp {
+            *lastmod = timestamp.to_string();
+        }
+    };
+
+    let newest = images.iter().map(|img| img.timestamp.as_str()).max().unwrap_or_default();
+    for page in PAGES {
+        add(page.to_string(), newest);
+    }
+
+    let cats = state.catalog.cats();
+    for image in &images {
+        add(format!("/photos/{}", image.id), &image.timestamp);
+        add(city_href(image), &image.timestamp);
+
+        for cat in cats.iter().filter(|cat| image.cat_ids.contains(&cat.id)) {
+            add(format!("/cats/by-name/{}", cat.slug), &image.timestamp);
+        }
+    }
+
+    // collections of photos that have since been unpublished would be empty pages
+    let is_public = |hash: &String| images.iter().any(|img: &Image| img.sha256 == *hash);
+    for collection in state.collections.all() {
+        if collection.hashes.iter().any(is_public) {
+            add(format!("/collections/{}", collection.id), &collection.updated);
+        }
+    }
+
+    let mut sitemap = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#, "\n",
+    ));
+
+    for (path, timestamp) in &pages {
+        let _ = write!(sitemap, "<url><loc>{}</loc>", escape_xml(&format!("{base_url}{path}")));
+        if !timestamp.is_empty() {
+            let _ = write!(sitemap, "<lastmod>{}</lastmod>", escape_xml(day(timestamp)));
+        }
+        sitemap.push_str("</url>\n");
+    }
+    sitemap.push_str("</urlset>\n");
+
+    ([(header::CONTENT_TYPE, "application/xml")], sitemap)
+}
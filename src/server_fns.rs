@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+
+use axum::body::{boxed, Bytes, Full};
+use axum::extract::{Path, RawQuery, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use leptos::leptos_server::{server_fn_by_path, Payload};
+use leptos::server_fn::Encoding;
+use leptos::{create_runtime, provide_context, use_context};
+use leptos_axum::{generate_request_and_parts, ResponseOptions};
+use tokio_util::task::LocalPoolHandle;
+use tracing::{Instrument, Span};
+
+use crate::rate_limit::ClientAddress;
+use crate::state::AppState;
+
+/// Server functions return futures that aren't `Send`, so they run on a pool of single threaded
+/// runtimes, like `leptos_axum` runs them.
+fn pool() -> &'static LocalPoolHandle {
+    static POOL: OnceLock<LocalPoolHandle> = OnceLock::new();
+    POOL.get_or_init(|| {
+        LocalPoolHandle::new(std::thread::available_parallelism().map(Into::into).unwrap_or(1))
+    })
+}
+
+/// Runs the server function at `/api/*fn_name` or `/admin/api/*fn_name`.
+///
+/// Does what `leptos_axum::handle_server_fns_with_context` does, but runs the server function in
+/// the request's span: it runs on another task, so everything it logs would lose the request id
+/// otherwise, and `leptos_axum` offers no way to instrument that task. Errors are logged too.
+pub async fn server_fn_handler(
+    State(state): State<AppState>,
+    Path(fn_name): Path<String>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    request: Request<axum::body::Body>,
+) -> Response {
+    let function = fn_name.trim_start_matches('/').to_string();
+    let address = ClientAddress(state.rate_limiter.address(&state, &request));
+    let span = Span::current();
+
+    let response = pool()
+        .spawn_pinned({
+            let function = function.clone();
+            move || call(state, address, function, headers, query, request).instrument(span)
+        })
+        .await
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+
+    crate::telemetry::log_server_fn_error(&function, response).await
+}
+
+async fn call(
+    state: AppState,
+    address: ClientAddress,
+    function: String,
+    headers: HeaderMap,
+    query: Option<String>,
+    request: Request<axum::body::Body>,
+) -> Response {
+    let Some(server_fn) = server_fn_by_path(&function) else {
+        return (StatusCode::BAD_REQUEST, format!("no server function at {function}")).into_response();
+    };
+
+    let runtime = create_runtime();
+    provide_context(state);
+    provide_context(address);
+    let (_, parts) = generate_request_and_parts(request).await;
+    provide_context(parts.clone());
+    provide_context(ResponseOptions::default());
+
+    let query = Bytes::from(query.unwrap_or_default());
+    let data = match server_fn.encoding() {
+        Encoding::Url | Encoding::Cbor => &parts.body,
+        Encoding::GetJSON | Encoding::GetCBOR => &query,
+    };
+
+    let response = match server_fn.call((), data).await {
+        Ok(payload) => respond(&headers, payload),
+        Err(e) => {
+            let body = serde_json::to_string(&e).unwrap_or_else(|_| e.to_string());
+            (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        }
+    };
+    runtime.dispose();
+    response
+}
+
+/// Answers fetches with the payload, and redirects forms that were submitted without JavaScript
+/// back to where they came from. Status and headers the server function set take precedence.
+fn respond(headers: &HeaderMap, payload: Payload) -> Response {
+    let accept = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok());
+    let mut response = match accept {
+        Some("application/json" | "application/x-www-form-urlencoded" | "application/cbor") => {
+            StatusCode::OK.into_response()
+        }
+        _ => {
+            let referer = headers.get(header::REFERER).and_then(|value| value.to_str().ok());
+            let mut response = StatusCode::SEE_OTHER.into_response();
+            if let Ok(location) = referer.unwrap_or("/").parse() {
+                response.headers_mut().insert(header::LOCATION, location);
+            }
+            response
+        }
+    };
+
+    let (content_type, body) = match payload {
+        Payload::Binary(data) => ("application/cbor", data),
+        Payload::Url(data) => ("application/x-www-form-urlencoded", data.into_bytes()),
+        Payload::Json(data) => ("application/json", data.into_bytes()),
+    };
+    response.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+    *response.body_mut() = boxed(Full::from(body));
+
+    if let Some(options) = use_context::<ResponseOptions>() {
+        let options = options.0.read();
+        if let Some(status) = options.status {
+            *response.status_mut() = status;
+        }
+        response.headers_mut().extend(options.headers.clone());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_fetches_and_redirects_forms() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        let response = respond(&headers, Payload::Json("{}".into()));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::REFERER, "/admin/photos".parse().unwrap());
+        let response = respond(&headers, Payload::Url(String::new()));
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/admin/photos");
+    }
+}
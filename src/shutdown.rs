@@ -0,0 +1,59 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::Router;
+use tokio::sync::oneshot;
+
+/// Resolves on SIGINT, i.e. Ctrl+C, or SIGTERM, which is what container runtimes send before
+/// they kill the process.
+#[cfg(unix)]
+pub async fn signal() {
+    use tokio::signal::unix::{signal as unix_signal, SignalKind};
+
+    let mut terminate = unix_signal(SignalKind::terminate()).expect("couldn't listen for SIGTERM");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => log::info!("received SIGINT, shutting down"),
+        _ = terminate.recv() => log::info!("received SIGTERM, shutting down"),
+    }
+}
+
+/// Resolves on Ctrl+C, there is no SIGTERM elsewhere.
+#[cfg(not(unix))]
+pub async fn signal() {
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => log::info!("received Ctrl+C, shutting down"),
+        Err(e) => panic!("couldn't listen for Ctrl+C: {e}"),
+    }
+}
+
+/// Serves `app` until [`signal`], then stops accepting connections and waits up to `timeout` for
+/// the requests in flight to finish.
+///
+/// There is nothing to flush afterwards: everything the server keeps is written to disk, atomically,
+/// before the request changing it returns, so requests that are cut short leave the previous state
+/// behind.
+pub async fn serve(app: Router, addr: SocketAddr, timeout: Duration) {
+    let (signalled, draining) = oneshot::channel();
+
+    let server = axum::Server::bind(&addr)
+        // the client's address, for rate limiting
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async {
+            signal().await;
+            let _ = signalled.send(());
+        });
+
+    let deadline = async {
+        let _ = draining.await;
+        tokio::time::sleep(timeout).await;
+    };
+
+    tokio::select! {
+        result = server => match result {
+            Ok(()) => log::info!("all requests finished"),
+            Err(e) => panic!("server failed: {e}"),
+        },
+        _ = deadline => log::warn!("requests still running after {}s, giving up on them", timeout.as_secs()),
+    }
+}
@@ -0,0 +1,107 @@
+use leptos::*;
+use leptos_meta::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::lightbox::PHOTO_PARAM;
+
+const SITE_NAME: &str = "Cats of Asia";
+
+/// The query parameter of links shared from a map popup.
+const SHARED_PHOTO_PARAM: &str = "imageId";
+
+/// What messengers and social networks show when someone shares a link to a photo.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PhotoPreview {
+    pub title: String,
+    pub description: String,
+    /// Absolute, crawlers don't resolve relative URLs.
+    pub image_url: String,
+    pub url: String,
+}
+
+#[server(GetPhotoPreview, "/api")]
+pub async fn get_photo_preview(id: usize) -> Result<Option<PhotoPreview>, ServerFnError> {
+    use crate::cats::cat_names;
+    use crate::map::format_location;
+
+    let state = crate::state::use_app_state()?;
+
+    let Some(image) = state.public_images().into_iter().find(|img| img.id == id) else {
+        return Ok(None);
+    };
+
+    let base_url = match use_context::<leptos_axum::RequestParts>() {
+        Some(parts) => crate::export::base_url(&state, &parts.headers),
+        None => state.public_url.clone().unwrap_or_default(),
+    };
+
+    let location = format_location(&image);
+    let names = cat_names(&state.catalog.cats(), &image.cat_ids);
+    let title = match names.is_empty() {
+        true => format!("Cat photo #{} from {location}", image.id),
+        false => format!("{} in {location}", names.join(" and ")),
+    };
+
+    let date = image.timestamp.split('T').next().unwrap_or_default();
+    let description = match &image.caption {
+        Some(caption) => format!("{caption} Taken in {location} on {date}."),
+        None => format!("Taken in {location} on {date}."),
+    };
+
+    Ok(Some(PhotoPreview {
+        title,
+        description,
+        image_url: format!("{base_url}{}", image.url_medium),
+        url: format!("{base_url}/photos/{}", image.id),
+    }))
+}
+
+/// OpenGraph and Twitter card tags for the photo `id`. The preview is loaded before the `<head>`
+/// is sent, as crawlers don't run any JavaScript.
+#[component]
+pub fn PhotoMeta(#[prop(into)] id: Signal<Option<usize>>) -> impl IntoView {
+    let preview = create_blocking_resource(id, |id| async move {
+        match id {
+            Some(id) => get_photo_preview(id).await.ok().flatten(),
+            None => None,
+        }
+    });
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || preview.get().flatten().map(|preview| view! { <PreviewTags preview/> })}
+        </Suspense>
+    }
+}
+
+/// [`PhotoMeta`] for links to a photo in the lightbox, or shared from the map.
+#[component]
+pub fn DeepLinkMeta() -> impl IntoView {
+    let query = use_query_map();
+    let id = Signal::derive(move || query.with(|q| {
+        q.get(PHOTO_PARAM)
+            .or_else(|| q.get(SHARED_PHOTO_PARAM))
+            .and_then(|id| id.parse().ok())
+    }));
+
+    view! { <PhotoMeta id/> }
+}
+
+#[component]
+fn PreviewTags(preview: PhotoPreview) -> impl IntoView {
+    let PhotoPreview { title, description, image_url, url } = preview;
+
+    view! {
+        <Meta property="og:site_name" content=SITE_NAME/>
+        <Meta property="og:type" content="article"/>
+        <Meta property="og:title" content=title.clone()/>
+        <Meta property="og:description" content=description.clone()/>
+        <Meta property="og:image" content=image_url.clone()/>
+        <Meta property="og:url" content=url/>
+        <Meta name="twitter:card" content="summary_large_image"/>
+        <Meta name="twitter:title" content=title/>
+        <Meta name="twitter:description" content=description/>
+        <Meta name="twitter:image" content=image_url/>
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use axum::extract::FromRef;
+use leptos::{use_context, LeptosOptions, ServerFnError};
+
+use crate::audit::AuditLog;
+use crate::auth::AdminCredentials;
+use crate::api::Image;
+use crate::catalog::Catalog;
+use crate::collection_store::CollectionStore;
+use crate::map::MapSettings;
+use crate::metrics::Metrics;
+use crate::privacy::PrivacySettings;
+use crate::rate_limit::RateLimiter;
+use crate::seo::RobotsSettings;
+
+/// Everything the server needs to handle a request. It is the axum router state and also
+/// provided as context to server functions and SSR rendering.
+#[derive(Clone, Debug)]
+pub struct AppState {
+    pub leptos_options: LeptosOptions,
+    pub catalog: Catalog,
+    pub collections: CollectionStore,
+    pub audit: AuditLog,
+    pub media_dir: PathBuf,
+    /// See [`Config::public_url`](crate::config::Config::public_url).
+    pub public_url: Option<String>,
+    /// See [`Config::trust_proxy`](crate::config::Config::trust_proxy).
+    pub trust_proxy: bool,
+    pub map: MapSettings,
+    pub admin: Option<AdminCredentials>,
+    pub privacy: PrivacySettings,
+    pub robots: RobotsSettings,
+    pub metrics: Metrics,
+    pub rate_limiter: RateLimiter,
+}
+
+impl AppState {
+    /// The published images as visitors get to see them, with locations coarsened according to
+    /// the privacy settings. Admin pages use the catalog directly.
+    pub fn public_images(&self) -> Vec<Image> {
+        self.catalog
+            .published_images()
+            .into_iter()
+            .map(|img| self.privacy.publish(img))
+            .collect()
+    }
+}
+
+impl FromRef<AppState> for LeptosOptions {
+    fn from_ref(state: &AppState) -> Self {
+        state.leptos_options.clone()
+    }
+}
+
+/// Returns the [`AppState`] from inside a server function.
+pub fn use_app_state() -> Result<AppState, ServerFnError> {
+    use_context::<AppState>()
+        .ok_or_else(|| ServerFnError::ServerError("app state missing from context".into()))
+}
@@ -0,0 +1,312 @@
+use leptos::*;
+use leptos_meta::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::Image;
+use crate::map::format_location;
+
+const BUSIEST_DAYS: usize = 5;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+const CHART_WIDTH: f64 = 600.0;
+const BAR_HEIGHT: f64 = 20.0;
+const LABEL_WIDTH: f64 = 160.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Count {
+    pub label: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    pub photos: usize,
+    /// Cats told apart on at least one photo.
+    pub cats: usize,
+    /// Cats seen in each country, most cats first. A cat seen in several countries counts for
+    /// each of them.
+    pub per_country: Vec<Count>,
+    /// Cats seen in each city, most cats first.
+    pub per_city: Vec<Count>,
+    /// Every month between the first and the most recent photo, oldest first.
+    pub per_month: Vec<Count>,
+    pub busiest_days: Vec<Count>,
+    /// The sum of the distances between consecutive photos.
+    pub total_distance_km: f64,
+    pub longest_hop_km: f64,
+    pub first: Option<Image>,
+    pub latest: Option<Image>,
+}
+
+impl Stats {
+    pub fn compute(mut images: Vec<Image>) -> Stats {
+        images.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+        let per_country = count_cats_by(&images, |img| crate::places::country_name(&img.country).to_string());
+        let per_city = count_cats_by(&images, format_location);
+        let mut busiest_days = count_by(&images, |img| day(&img.timestamp).to_string());
+        busiest_days.truncate(BUSIEST_DAYS);
+
+        let hops: Vec<f64> = images.windows(2)
+            .map(|pair| distance_km(&pair[0], &pair[1]))
+            .collect();
+
+        Stats {
+            photos: images.len(),
+            cats: distinct_cats(images.iter()),
+            per_country,
+            per_city,
+            per_month: per_month(&images),
+            busiest_days,
+            total_distance_km: hops.iter().sum(),
+            longest_hop_km: hops.iter().copied().fold(0.0, f64::max),
+            first: images.first().cloned(),
+            latest: images.last().cloned(),
+        }
+    }
+
+    pub fn average_hop_km(&self) -> f64 {
+        if self.photos < 2 {
+            0.0
+        } else {
+            self.total_distance_km / (self.photos - 1) as f64
+        }
+    }
+}
+
+/// Counts images per `key`, most frequent first and alphabetically for ties.
+fn count_by(images: &[Image], key: impl Fn(&Image) -> String) -> Vec<Count> {
+    let mut counts: Vec<Count> = vec![];
+    for image in images {
+        let label = key(image);
+        match counts.iter_mut().find(|c| c.label == label) {
+            Some(count) => count.count += 1,
+            None => counts.push(Count { label, count: 1 }),
+        }
+    }
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.label.cmp(&b.label)));
+    counts
+}
+
+/// Counts the distinct cats on the images per `key`, most cats first and alphabetically for ties.
+/// Keys without a known cat are left out.
+fn count_cats_by(images: &[Image], key: impl Fn(&Image) -> String) -> Vec<Count> {
+    let mut keys: Vec<String> = images.iter().map(&key).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut counts: Vec<Count> = keys.into_iter()
+        .map(|label| Count {
+            count: distinct_cats(images.iter().filter(|img| key(img) == label)),
+            label,
+        })
+        .filter(|count| count.count > 0)
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.label.cmp(&b.label)));
+    counts
+}
+
+fn distinct_cats<'a>(images: impl Iterator<Item = &'a Image>) -> usize {
+    let mut ids: Vec<usize> = images.flat_map(|img| img.cat_ids.iter().copied()).collect();
+    ids.sort();
+    ids.dedup();
+    ids.len()
+}
+
+/// Expects `images` to be sorted by timestamp. Includes months without photos, so gaps show up
+/// in the chart.
+fn per_month(images: &[Image]) -> Vec<Count> {
+    let months: Vec<(i32, u32)> = images.iter().filter_map(|img| month(&img.timestamp)).collect();
+    let (Some(&first), Some(&last)) = (months.first(), months.last()) else {
+        return vec![];
+    };
+
+    let mut counts = vec![];
+    let (mut year, mut month) = first;
+    while (year, month) <= last {
+        counts.push(Count {
+            label: format!("{year}-{month:02}"),
+            count: months.iter().filter(|&&m| m == (year, month)).count(),
+        });
+        (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    }
+    counts
+}
+
+fn day(timestamp: &str) -> &str {
+    timestamp.split('T').next().unwrap_or_default()
+}
+
+fn month(timestamp: &str) -> Option<(i32, u32)> {
+    let mut parts = timestamp.split('-');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Great-circle distance using the haversine formula.
+fn distance_km(a: &Image, b: &Image) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+#[server(GetStats, "/api")]
+pub async fn get_stats() -> Result<Stats, ServerFnError> {
+    let state = crate::state::use_app_state()?;
+    Ok(Stats::compute(state.public_images()))
+}
+
+#[component]
+pub fn StatsPage() -> impl IntoView {
+    let stats = create_resource(|| (), |_| get_stats());
+
+    view! {
+        <Title text="Statistics - Cats of Asia"/>
+        <h1>"Statistics"</h1>
+        <Suspense fallback=|| view! { <progress></progress> }>
+            {move || stats.get().map(|stats| match stats {
+                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                Ok(stats) => view! { <StatsView stats/> }.into_view(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn StatsView(stats: Stats) -> impl IntoView {
+    let photo_summary = |image: Option<Image>| image.map(|img| {
+        let href = format!("/photos/{}", img.id);
+        view! {
+            <a href=href>"#"{img.id}</a>" on "{day(&img.timestamp).to_string()}" in "{format_location(&img)}
+        }
+    });
+    let average_hop = stats.average_hop_km();
+
+    view! {
+        <div class="grid">
+            <article>
+                <header>"Photos"</header>
+                <strong>{stats.photos}</strong>
+            </article>
+            <article>
+                <header>"Cats"</header>
+                <strong>{stats.cats}</strong>
+            </article>
+            <article>
+                <header>"Travelled"</header>
+                <strong>{format!("{:.0} km", stats.total_distance_km)}</strong>
+                <p>
+                    {format!("{average_hop:.1} km between photos on average, ")}
+                    {format!("{:.0} km at most", stats.longest_hop_km)}
+                </p>
+            </article>
+        </div>
+        <p>"First photo: "{photo_summary(stats.first)}</p>
+        <p>"Most recent photo: "{photo_summary(stats.latest)}</p>
+
+        <h2>"Photos per month"</h2>
+        <ColumnChart counts=stats.per_month/>
+        <h2>"Cats per country"</h2>
+        <BarChart counts=stats.per_country/>
+        <h2>"Cats per city"</h2>
+        <BarChart counts=stats.per_city/>
+        <h2>"Busiest days"</h2>
+        <BarChart counts=stats.busiest_days/>
+    }
+}
+
+/// Horizontal bars with a label on the left, for charts with long labels.
+#[component]
+fn BarChart(counts: Vec<Count>) -> impl IntoView {
+    let max = counts.iter().map(|c| c.count).max().unwrap_or(1) as f64;
+    let height = counts.len() as f64 * BAR_HEIGHT;
+    let bar_space = CHART_WIDTH - LABEL_WIDTH - 40.0;
+
+    let bars = counts.into_iter()
+        .enumerate()
+        .map(|(i, Count { label, count })| {
+            let y = i as f64 * BAR_HEIGHT;
+            let width = count as f64 / max * bar_space;
+            view! {
+                <g>
+                    <text x=LABEL_WIDTH - 5.0 y=y + BAR_HEIGHT * 0.7 text-anchor="end">{label}</text>
+                    <rect x=LABEL_WIDTH y=y + 2.0 width=width height=BAR_HEIGHT - 4.0/>
+                    <text x=LABEL_WIDTH + width + 5.0 y=y + BAR_HEIGHT * 0.7>{count}</text>
+                </g>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <svg class="chart" viewBox=format!("0 0 {CHART_WIDTH} {height}") role="img">
+            {bars}
+        </svg>
+    }
+}
+
+/// Vertical columns, for time series.
+#[component]
+fn ColumnChart(counts: Vec<Count>) -> impl IntoView {
+    const HEIGHT: f64 = 150.0;
+    const LABELS: f64 = 30.0;
+
+    let max = counts.iter().map(|c| c.count).max().unwrap_or(1) as f64;
+    let column_width = CHART_WIDTH / counts.len().max(1) as f64;
+    // label every column if there's room, otherwise only every January
+    let label_all = column_width >= 40.0;
+
+    let columns = counts.into_iter()
+        .enumerate()
+        .map(|(i, Count { label, count })| {
+            let x = i as f64 * column_width;
+            let height = count as f64 / max * (HEIGHT - LABELS);
+            let show_label = label_all || label.ends_with("-01");
+            view! {
+                <g>
+                    <title>{format!("{label}: {count}")}</title>
+                    <rect x=x + 1.0 y=HEIGHT - LABELS - height width=(column_width - 2.0).max(1.0) height=height/>
+                    {show_label.then(|| view! {
+                        <text x=x y=HEIGHT - 5.0>{label}</text>
+                    })}
+                </g>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <svg class="chart" viewBox=format!("0 0 {CHART_WIDTH} {HEIGHT}") role="img">
+            {columns}
+        </svg>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(city: &str, country: &str, cat_ids: &[usize]) -> Image {
+        Image { city: city.into(), country: country.into(), cat_ids: cat_ids.to_vec(), ..Default::default() }
+    }
+
+    #[test]
+    fn counts_each_cat_once_per_place() {
+        let stats = Stats::compute(vec![
+            image("Bangkok", "Thailand", &[1, 2]),
+            image("Bangkok", "Thailand", &[1]),
+            image("Chiang Mai", "Thailand", &[1, 3]),
+            image("Hanoi", "Vietnam", &[4]),
+            image("Hue", "Vietnam", &[]),
+        ]);
+
+        assert_eq!(stats.cats, 4);
+        let counts = |counts: &[Count]| counts.iter().map(|c| (c.label.clone(), c.count)).collect::<Vec<_>>();
+        assert_eq!(counts(&stats.per_country), [("Thailand".into(), 3), ("Vietnam".into(), 1)]);
+        assert_eq!(counts(&stats.per_city), [
+            ("Bangkok, Thailand".into(), 2),
+            ("Chiang Mai, Thailand".into(), 2),
+            ("Hanoi, Vietnam".into(), 1),
+        ]);
+    }
+}
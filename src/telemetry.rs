@@ -0,0 +1,103 @@
+use axum::body::{boxed, Full, HttpBody};
+use axum::extract::MatchedPath;
+use axum::http::Request;
+use axum::response::Response;
+use leptos::use_context;
+use serde::Deserialize;
+use tracing::{info_span, Span};
+use tracing_subscriber::EnvFilter;
+
+/// Set on every request by `SetRequestIdLayer` and sent back with the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human readable line per event, for terminals.
+    #[default]
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+/// How the server logs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LogSettings {
+    pub format: LogFormat,
+    /// What to log, in `RUST_LOG` syntax, e.g. `info,cats_of_asia=debug`.
+    pub filter: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            format: LogFormat::default(),
+            filter: "info".to_string(),
+        }
+    }
+}
+
+/// Installs the global subscriber. Records from the `log` macros end up there too.
+pub fn init(settings: &LogSettings) {
+    // already validated with the rest of the configuration
+    let filter = EnvFilter::try_new(&settings.filter).unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match settings.format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().flatten_event(true).with_span_list(false).init(),
+    }
+}
+
+/// The span everything logged while handling `request` belongs to, so all of it can be found by
+/// the request id a visitor reports.
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    let route = request.extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or(crate::metrics::UNMATCHED);
+
+    info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        path = request.uri().path(),
+    )
+}
+
+/// The id of the request being handled, from inside a server function or while rendering a page.
+pub fn request_id() -> Option<String> {
+    use_context::<leptos_axum::RequestParts>()?
+        .headers
+        .get(REQUEST_ID_HEADER)?
+        .to_str()
+        .ok()
+        .map(str::to_string)
+}
+
+/// Logs the error a server function returned, in the request's span.
+pub async fn log_server_fn_error(function: &str, response: Response) -> Response {
+    if !response.status().is_server_error() && !response.status().is_client_error() {
+        return response;
+    }
+
+    let (parts, mut body) = response.into_parts();
+    let mut bytes = Vec::new();
+    while let Some(Ok(chunk)) = body.data().await {
+        bytes.extend_from_slice(&chunk);
+    }
+
+    tracing::warn!(
+        function,
+        status = parts.status.as_u16(),
+        error = %String::from_utf8_lossy(&bytes),
+        "server function failed",
+    );
+    Response::from_parts(parts, boxed(Full::from(bytes)))
+}